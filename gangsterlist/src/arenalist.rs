@@ -0,0 +1,557 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+/*
+* ArenaGList is GList without the wizard magic.
+*
+* Instead of every node being its own Box somewhere on the heap, all of the
+* nodes live next to each other in one Vec, and the front/back links are just
+* u32 indices into it. Removed nodes leave a Vacant slot behind which goes on
+* a free list (chained through the vacant slots themselves), so the next push
+* fills the hole instead of growing the Vec.
+*
+* That buys us:
+*
+* No unsafe at all, a bad index is a panic instead of undefined behaviour.
+* Better cache locality, nodes pushed together sit together.
+* Clone is just cloning the Vec.
+*
+* The public API is the same as GList's push_*, pop_*, front/back, iter,
+* iter_mut, into_iter, len, and the same set of traits.
+* The catch is at most u32::MAX - 1 slots, and iter_mut has to do an O(n)
+* setup to split the borrow of the slab up without unsafe.
+*/
+
+#[derive(Clone)]
+pub struct ArenaGList<T> {
+    slots: Vec<Slot<T>>,
+    front: Link,
+    back: Link,
+    // Head of the chain of Vacant slots
+    free: Link,
+    len: usize,
+}
+
+type Link = Option<u32>;
+
+#[derive(Clone)]
+enum Slot<T> {
+    Occupied(Node<T>),
+    Vacant { next_free: Link },
+}
+
+#[derive(Clone)]
+struct Node<T> {
+    front: Link,
+    back: Link,
+    elem: T,
+}
+
+pub struct Iter<'a, T> {
+    slots: &'a [Slot<T>],
+    front: Link,
+    back: Link,
+    len: usize,
+}
+
+pub struct IterMut<'a, T> {
+    // Every slot's node, pulled out of the slab one by one as we visit it
+    nodes: Vec<Option<&'a mut Node<T>>>,
+    front: Link,
+    back: Link,
+    len: usize,
+}
+
+pub struct IntoIter<T> {
+    list: ArenaGList<T>,
+}
+
+impl<T> ArenaGList<T> {
+
+    pub fn new() -> Self {
+        ArenaGList {
+            slots: Vec::new(),
+            front: None,
+            back: None,
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        ArenaGList {
+            slots: Vec::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        let new = self.alloc(Node {
+            front: None,
+            back: self.front,
+            elem,
+        });
+
+        if let Some(old) = self.front {
+            self.node_mut(old).front = Some(new);
+        } else {
+            self.back = Some(new);
+        }
+
+        self.front = Some(new);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        let new = self.alloc(Node {
+            front: self.back,
+            back: None,
+            elem,
+        });
+
+        if let Some(old) = self.back {
+            self.node_mut(old).back = Some(new);
+        } else {
+            self.front = Some(new);
+        }
+
+        self.back = Some(new);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let old = self.front?;
+        let node = self.release(old);
+
+        self.front = node.back;
+        if let Some(new) = self.front {
+            self.node_mut(new).front = None;
+        } else {
+            self.back = None;
+        }
+
+        self.len -= 1;
+        Some(node.elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let old = self.back?;
+        let node = self.release(old);
+
+        self.back = node.front;
+        if let Some(new) = self.back {
+            self.node_mut(new).back = None;
+        } else {
+            self.front = None;
+        }
+
+        self.len -= 1;
+        Some(node.elem)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.front.map(|index| &self.node(index).elem)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.front.map(|index| &mut self.node_mut(index).elem)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.back.map(|index| &self.node(index).elem)
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.back.map(|index| &mut self.node_mut(index).elem)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            slots: &self.slots,
+            front: self.front,
+            back: self.back,
+            len: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let nodes = self
+            .slots
+            .iter_mut()
+            .map(|slot| match slot {
+                Slot::Occupied(node) => Some(node),
+                Slot::Vacant { .. } => None,
+            })
+            .collect();
+
+        IterMut {
+            nodes,
+            front: self.front,
+            back: self.back,
+            len: self.len,
+        }
+    }
+
+    // Keeps the slab's memory around for whatever gets pushed next
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.front = None;
+        self.back = None;
+        self.free = None;
+        self.len = 0;
+    }
+
+    // How many nodes fit before the slab has to grow
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    fn node(&self, index: u32) -> &Node<T> {
+        match &self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => panic!("ArenaGList link points at a vacant slot"),
+        }
+    }
+
+    fn node_mut(&mut self, index: u32) -> &mut Node<T> {
+        match &mut self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => panic!("ArenaGList link points at a vacant slot"),
+        }
+    }
+
+    // Drops node into a vacant slot if there is one, otherwise grows the slab
+    fn alloc(&mut self, node: Node<T>) -> u32 {
+        if let Some(index) = self.free {
+            let slot = &mut self.slots[index as usize];
+            let Slot::Vacant { next_free } = *slot else {
+                panic!("ArenaGList free list points at an occupied slot");
+            };
+
+            self.free = next_free;
+            *slot = Slot::Occupied(node);
+            index
+        } else {
+            // u32::MAX stays unused so a slot count always fits in a u32 too
+            let index = u32::try_from(self.slots.len())
+                .ok()
+                .filter(|&index| index < u32::MAX)
+                .expect("ArenaGList is out of u32 indices");
+
+            self.slots.push(Slot::Occupied(node));
+            index
+        }
+    }
+
+    // Takes the node out of its slot and puts the slot on the free list
+    fn release(&mut self, index: u32) -> Node<T> {
+        let vacant = Slot::Vacant {
+            next_free: self.free,
+        };
+
+        match std::mem::replace(&mut self.slots[index as usize], vacant) {
+            Slot::Occupied(node) => {
+                self.free = Some(index);
+                node
+            }
+            Slot::Vacant { .. } => panic!("ArenaGList released a vacant slot"),
+        }
+    }
+}
+
+impl<T> Default for ArenaGList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for ArenaGList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push_back(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for ArenaGList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug> Debug for ArenaGList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+// Two lists holding the same things in the same order are equal,
+// no matter how their slabs happen to be laid out
+impl<T: PartialEq> PartialEq for ArenaGList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for ArenaGList<T> {}
+
+impl<T: PartialOrd> PartialOrd for ArenaGList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for ArenaGList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<T: Hash> Hash for ArenaGList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for item in self {
+            item.hash(state)
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ArenaGList<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut ArenaGList<T> {
+    type IntoIter = IterMut<'a, T>;
+    type Item = &'a mut T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T> IntoIterator for ArenaGList<T> {
+    type IntoIter = IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn node(&self, index: u32) -> &'a Node<T> {
+        match &self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => panic!("ArenaGList link points at a vacant slot"),
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.front.map(|index| {
+                let node = self.node(index);
+                self.len -= 1;
+                self.front = node.back;
+                &node.elem
+            })
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.back.map(|index| {
+                let node = self.node(index);
+                self.len -= 1;
+                self.back = node.front;
+                &node.elem
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            let index = self.front?;
+            // Each node only ever gets taken once, that's what keeps the
+            // &mut we hand out unique
+            let node = self.nodes[index as usize].take()?;
+            self.len -= 1;
+            self.front = node.back;
+            Some(&mut node.elem)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            let index = self.back?;
+            let node = self.nodes[index as usize].take()?;
+            self.len -= 1;
+            self.back = node.front;
+            Some(&mut node.elem)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.list.len
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::ArenaGList;
+
+    // Same suite GList has to pass
+    crate::gangsterlist::deque_tests!(ArenaGList);
+
+    #[test]
+    fn reuses_slots() {
+        let mut list: ArenaGList<u32> = (0..4).collect();
+        assert_eq!(list.slots.len(), 4);
+
+        list.pop_front();
+        list.pop_back();
+        list.push_back(10);
+        list.push_front(11);
+        assert_eq!(list.slots.len(), 4);
+        list.push_back(12);
+        assert_eq!(list.slots.len(), 5);
+
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![11, 1, 2, 10, 12]);
+        assert_eq!(list.iter().rev().copied().collect::<Vec<_>>(), vec![12, 10, 2, 1, 11]);
+    }
+
+    #[test]
+    fn clone_and_eq_ignore_layout() {
+        // Same contents, different slab layout
+        let mut a: ArenaGList<u32> = ArenaGList::new();
+        a.push_back(2);
+        a.push_back(3);
+        a.push_front(1);
+        let b: ArenaGList<u32> = (1..4).collect();
+        assert_eq!(a, b);
+
+        let mut c = a.clone();
+        assert_eq!(a, c);
+        *c.front_mut().unwrap() = 100;
+        assert_eq!(a.front(), Some(&1));
+        assert_eq!(c.front(), Some(&100));
+    }
+
+    #[test]
+    fn into_iter_and_clear() {
+        let list: ArenaGList<u32> = (0..5).collect();
+        let mut it = list.clone().into_iter();
+        assert_eq!(it.len(), 5);
+        assert_eq!(it.next(), Some(0));
+        assert_eq!(it.next_back(), Some(4));
+        assert_eq!(it.collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let mut list = list;
+        let capacity = list.capacity();
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list.front(), None);
+        assert_eq!(list.capacity(), capacity);
+        list.push_back(7);
+        assert_eq!(list.back(), Some(&7));
+    }
+
+    #[test]
+    fn iter_mut_after_churn() {
+        let mut list: ArenaGList<u32> = ArenaGList::new();
+        for i in 0..10 {
+            list.push_back(i);
+            if i % 3 == 0 {
+                list.pop_front();
+            }
+        }
+        for elem in &mut list {
+            *elem *= 10;
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![40, 50, 60, 70, 80, 90]);
+
+        let mut it = list.iter_mut();
+        *it.next_back().unwrap() = 0;
+        assert_eq!(it.len(), 5);
+        assert_eq!(list.back(), Some(&0));
+    }
+}
@@ -0,0 +1,477 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+
+use crate::gangsterlist::{self, CursorMut, GList};
+
+/*
+* CircularGList is a GList where the back wraps round to the front, for
+* token rings, round robin schedulers and the like.
+*
+* The ring has an anchor, the element iter() starts from. Underneath it's
+* just a GList whose front is the anchor and whose back is the element right
+* before it, so iter(), len(), Debug and == all work exactly like GList's,
+* starting from the anchor. Moving the anchor is a rotate.
+*
+* A RingCursor never falls off the end. Going past the last element puts it
+* back on the anchor (and the other way round), so it can go round forever.
+* remove_current() moves it onto the element after the one it took out, same
+* as a GList cursor, except that after the last element comes the anchor.
+*
+* The only time a RingCursor isn't on anything is when the ring is empty.
+*/
+
+pub struct CircularGList<T> {
+    list: GList<T>,
+}
+
+pub struct RingCursor<'a, T> {
+    cursor: CursorMut<'a, T>,
+}
+
+impl<T> CircularGList<T> {
+
+    pub fn new() -> Self {
+        CircularGList { list: GList::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn anchor(&self) -> Option<&T> {
+        self.list.front()
+    }
+
+    pub fn anchor_mut(&mut self) -> Option<&mut T> {
+        self.list.front_mut()
+    }
+
+    // The one right before the anchor, what iter() gets to last
+    pub fn last(&self) -> Option<&T> {
+        self.list.back()
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        self.list.back_mut()
+    }
+
+    // Counts from the anchor and keeps going round, so any index is fine
+    // as long as there's something in the ring
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.list.get(index % self.len())
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if self.is_empty() {
+            return None;
+        }
+        let len = self.len();
+        self.list.get_mut(index % len)
+    }
+
+    // Becomes the new anchor
+    pub fn push_front(&mut self, elem: T) {
+        self.list.push_front(elem);
+    }
+
+    // Goes in just before the anchor
+    pub fn push_back(&mut self, elem: T) {
+        self.list.push_back(elem);
+    }
+
+    // Takes the anchor, the one after it becomes the new anchor
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+
+    // Makes the element at index (counting from the current anchor) the
+    // anchor. Like GList's indexing, index >= len panics.
+    pub fn set_anchor(&mut self, index: usize) {
+        assert!(index < self.len(), "Cannot anchor at a nonexistent index");
+        self.list.rotate_left(index);
+    }
+
+    // Moves the anchor k steps forward, going round as many times as it takes
+    pub fn rotate_forward(&mut self, k: usize) {
+        if !self.is_empty() {
+            let k = k % self.len();
+            self.list.rotate_left(k);
+        }
+    }
+
+    pub fn rotate_backward(&mut self, k: usize) {
+        if !self.is_empty() {
+            let k = k % self.len();
+            self.list.rotate_right(k);
+        }
+    }
+
+    // Starts on the anchor
+    pub fn cursor_mut(&mut self) -> RingCursor<'_, T> {
+        let mut cursor = self.list.cursor_mut();
+        cursor.move_next();
+        RingCursor { cursor }
+    }
+
+    // Once round, starting at the anchor
+    pub fn iter(&self) -> gangsterlist::Iter<'_, T> {
+        self.list.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> gangsterlist::IterMut<'_, T> {
+        self.list.iter_mut()
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    // The ring cut open at the anchor
+    pub fn as_list(&self) -> &GList<T> {
+        &self.list
+    }
+
+    pub fn into_list(self) -> GList<T> {
+        self.list
+    }
+}
+
+impl<T> Default for CircularGList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The list's front becomes the anchor
+impl<T> From<GList<T>> for CircularGList<T> {
+    fn from(list: GList<T>) -> Self {
+        CircularGList { list }
+    }
+}
+
+impl<T: Clone> Clone for CircularGList<T> {
+    fn clone(&self) -> Self {
+        CircularGList {
+            list: self.list.clone(),
+        }
+    }
+}
+
+impl<T> Extend<T> for CircularGList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.list.extend(iter);
+    }
+}
+
+impl<T> FromIterator<T> for CircularGList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        CircularGList {
+            list: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for CircularGList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.list.fmt(f)
+    }
+}
+
+// Same elements starting from the anchor, just like GList. The same ring
+// anchored somewhere else isn't equal.
+impl<T: PartialEq> PartialEq for CircularGList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.list == other.list
+    }
+}
+
+impl<T: Eq> Eq for CircularGList<T> {}
+
+impl<T: Hash> Hash for CircularGList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.list.hash(state);
+    }
+}
+
+impl<'a, T> IntoIterator for &'a CircularGList<T> {
+    type IntoIter = gangsterlist::Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut CircularGList<T> {
+    type IntoIter = gangsterlist::IterMut<'a, T>;
+    type Item = &'a mut T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T> IntoIterator for CircularGList<T> {
+    type IntoIter = gangsterlist::IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.into_iter()
+    }
+}
+
+impl<'a, T> RingCursor<'a, T> {
+
+    // How many steps past the anchor we are, None only on an empty ring
+    pub fn index(&self) -> Option<usize> {
+        self.cursor.index()
+    }
+
+    // Past the last element is the anchor again
+    pub fn move_next(&mut self) {
+        self.cursor.move_next();
+        if self.cursor.index().is_none() {
+            // Hopped onto the ghost, step over it
+            self.cursor.move_next();
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        self.cursor.move_prev();
+        if self.cursor.index().is_none() {
+            self.cursor.move_prev();
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.cursor.current()
+    }
+
+    // On a ring of one, that's the current element again
+    pub fn peek_next(&self) -> Option<&T> {
+        let mut cursor = self.cursor.as_cursor();
+        cursor.move_next();
+        if cursor.index().is_none() {
+            cursor.move_next();
+        }
+        cursor.current()
+    }
+
+    pub fn peek_prev(&self) -> Option<&T> {
+        let mut cursor = self.cursor.as_cursor();
+        cursor.move_prev();
+        if cursor.index().is_none() {
+            cursor.move_prev();
+        }
+        cursor.current()
+    }
+
+    // On an empty ring the cursor moves onto the new element
+    pub fn insert_after(&mut self, elem: T) {
+        self.cursor.insert_after(elem);
+        if self.cursor.index().is_none() {
+            self.cursor.move_next();
+        }
+    }
+
+    // Before the anchor means the back, so the anchor stays the anchor
+    pub fn insert_before(&mut self, elem: T) {
+        match self.cursor.index() {
+            Some(0) => {
+                // Over the ghost, push_back from there, and back again
+                self.cursor.move_prev();
+                self.cursor.insert_before(elem);
+                self.cursor.move_next();
+            }
+            Some(_) => self.cursor.insert_before(elem),
+            None => {
+                self.cursor.insert_before(elem);
+                self.cursor.move_next();
+            }
+        }
+    }
+
+    // Takes out the current element and moves onto the next one. Taking
+    // the anchor makes the next one the anchor.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let elem = self.cursor.remove_current()?;
+        if self.cursor.index().is_none() {
+            // Took the last one, round to the front (if there's anything left)
+            self.cursor.move_next();
+        }
+        Some(elem)
+    }
+
+    // Makes whatever we're on the ring's anchor, without walking anywhere
+    pub fn make_anchor(&mut self) {
+        if matches!(self.cursor.index(), None | Some(0)) {
+            return;
+        }
+
+        // Cut off everything in front of us and hang it on the back
+        let before = self.cursor.split_before();
+        self.cursor.move_prev();
+        self.cursor.splice_before(before);
+        self.cursor.move_next();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::CircularGList;
+    use crate::gangsterlist::GList;
+
+    #[test]
+    fn basics() {
+        let mut ring = CircularGList::new();
+        assert!(ring.is_empty());
+        assert_eq!(ring.anchor(), None);
+        assert_eq!(ring.get(3), None);
+        ring.rotate_forward(5);
+
+        ring.push_back(2);
+        ring.push_back(3);
+        ring.push_front(1);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.anchor(), Some(&1));
+        assert_eq!(ring.last(), Some(&3));
+        assert_eq!(ring.get(4), Some(&2));
+        assert_eq!(format!("{:?}", ring), "[1, 2, 3]");
+
+        ring.rotate_forward(7);
+        assert_eq!(format!("{:?}", ring), "[2, 3, 1]");
+        ring.rotate_backward(2);
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![3, 1, 2]);
+        ring.set_anchor(1);
+        assert_eq!(ring.anchor(), Some(&1));
+
+        // Equal means equal from the anchor, same as GList
+        let other: CircularGList<_> = vec![2, 3, 1].into_iter().collect();
+        assert_ne!(ring, other);
+        let mut turned = other.clone();
+        turned.rotate_forward(2);
+        assert_eq!(ring, turned);
+        assert_eq!(ring.as_list(), &GList::from_iter([1, 2, 3]));
+
+        assert_eq!(ring.pop_front(), Some(1));
+        assert_eq!(ring.pop_back(), Some(3));
+        assert_eq!(ring.into_list().into_iter().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn cursor_goes_round_forever() {
+        let mut ring: CircularGList<u32> = (0..5).collect();
+        let mut cursor = ring.cursor_mut();
+
+        let mut seen = Vec::new();
+        for _ in 0..23 {
+            seen.push(*cursor.current().unwrap());
+            cursor.move_next();
+        }
+        assert!(seen.iter().copied().eq((0..23).map(|i| i % 5)));
+
+        for _ in 0..12 {
+            cursor.move_prev();
+        }
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(cursor.peek_prev(), Some(&0));
+
+        cursor.move_prev();
+        assert_eq!(cursor.peek_prev(), Some(&4));
+        cursor.move_prev();
+        assert_eq!(cursor.peek_next(), Some(&0));
+
+        let mut empty: CircularGList<u32> = CircularGList::new();
+        let mut cursor = empty.cursor_mut();
+        cursor.move_next();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.remove_current(), None);
+    }
+
+    #[test]
+    fn josephus() {
+        // Seven round a table, every third one leaves
+        let mut ring: CircularGList<u32> = (1..=7).collect();
+        let mut cursor = ring.cursor_mut();
+        let mut gone = Vec::new();
+
+        while cursor.peek_next().is_some() && gone.len() < 6 {
+            cursor.move_next();
+            cursor.move_next();
+            gone.push(cursor.remove_current().unwrap());
+        }
+        assert_eq!(gone, vec![3, 6, 2, 7, 5, 1]);
+        assert_eq!(cursor.current(), Some(&mut 4));
+        assert_eq!(cursor.peek_next(), Some(&4));
+
+        assert_eq!(cursor.remove_current(), Some(4));
+        assert_eq!(cursor.current(), None);
+        cursor.insert_after(9);
+        assert_eq!(cursor.current(), Some(&mut 9));
+        assert_eq!(ring.len(), 1);
+    }
+
+    #[test]
+    fn edits_around_the_anchor() {
+        let mut ring: CircularGList<u32> = (0..4).collect();
+
+        {
+            let mut cursor = ring.cursor_mut();
+            // Before the anchor is the back, the anchor doesn't change
+            cursor.insert_before(10);
+            assert_eq!(cursor.current(), Some(&mut 0));
+            cursor.move_prev();
+            cursor.move_prev();
+            assert_eq!(cursor.current(), Some(&mut 3));
+            // After the last one is also the back
+            cursor.insert_after(11);
+            cursor.move_next();
+            assert_eq!(cursor.current(), Some(&mut 11));
+            cursor.move_next();
+            assert_eq!(cursor.current(), Some(&mut 10));
+        }
+        assert_eq!(format!("{:?}", ring), "[0, 1, 2, 3, 11, 10]");
+
+        {
+            let mut cursor = ring.cursor_mut();
+            cursor.move_prev();
+            // Removing the last one wraps onto the anchor
+            assert_eq!(cursor.remove_current(), Some(10));
+            assert_eq!(cursor.index(), Some(0));
+            // Removing the anchor makes the next one the anchor
+            assert_eq!(cursor.remove_current(), Some(0));
+            assert_eq!(cursor.index(), Some(0));
+
+            cursor.move_next();
+            cursor.move_next();
+            cursor.make_anchor();
+            assert_eq!(cursor.index(), Some(0));
+            assert_eq!(cursor.current(), Some(&mut 3));
+            assert_eq!(cursor.peek_prev(), Some(&2));
+        }
+        assert_eq!(format!("{:?}", ring), "[3, 11, 1, 2]");
+        assert_eq!(ring.anchor(), Some(&3));
+
+        for x in &mut ring {
+            *x += 1;
+        }
+        assert_eq!(ring.iter().sum::<u32>(), 21);
+    }
+}
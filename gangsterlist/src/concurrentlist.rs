@@ -0,0 +1,580 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+/*
+* ConcurrentGList is a doubly linked list that lots of threads can walk and
+* change at the same time, without one big lock around the whole thing.
+*
+* Every node has its own Mutex guarding its elem and its links. Two sentinel
+* nodes sit at either end (they never hold an elem), so every real node
+* always has a neighbour on both sides to lock. Links point forward through
+* Arc and backward through Weak, so there's no cycle to leak.
+*
+* Changing a link means holding the lock of every node whose links change,
+* and the one rule that keeps this deadlock free:
+*
+*     Locks are only ever taken front to back.
+*
+* Anything that has to find its nodes from the back (push_back, pop_back)
+* peeks at the back briefly, lets go, then locks the nodes it found front to
+* back and checks they're still next to each other, starting over if not.
+*
+* Walking the list is hand over hand (lock coupling): lock the next node,
+* then let go of the one you're on. The cursor does that, and it holds the
+* node it's on locked the whole time, so nothing can be put in or taken out
+* right after it. insert() and remove() walk a cursor to the spot.
+* Cursors only go forwards, going backwards would break the lock order.
+*
+* snapshot() clones everything during one hand over hand walk. It's not a
+* frozen picture of the whole list, things can change behind the walk, but
+* anything that was in the list the entire time shows up, in order.
+*
+* Don't use the list from the same thread while that thread holds a cursor,
+* it'll wait on the cursor's own lock forever.
+*/
+
+pub struct ConcurrentGList<T> {
+    head: Arc<Node<T>>,
+    tail: Arc<Node<T>>,
+    len: AtomicUsize,
+}
+
+struct Node<T> {
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    // None for the sentinels, and for nodes that have been popped
+    elem: Option<T>,
+    // Only the tail sentinel and unlinked nodes have None
+    next: Option<Arc<Node<T>>>,
+    prev: Weak<Node<T>>,
+    // Cleared when the node gets taken out, so stale peeks can tell
+    linked: bool,
+}
+
+pub struct CursorMut<'a, T> {
+    // Borrows out of node, so it has to be declared (and dropped) first
+    guard: MutexGuard<'a, State<T>>,
+    node: Arc<Node<T>>,
+    list: &'a ConcurrentGList<T>,
+}
+
+pub struct Snapshot<T> {
+    inner: std::vec::IntoIter<T>,
+}
+
+impl<T> Node<T> {
+    fn new(elem: Option<T>, next: Option<Arc<Node<T>>>, prev: Weak<Node<T>>) -> Arc<Node<T>> {
+        Arc::new(Node {
+            state: Mutex::new(State {
+                elem,
+                next,
+                prev,
+                linked: true,
+            }),
+        })
+    }
+
+    // A panic somewhere else doesn't make the links any less valid
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> ConcurrentGList<T> {
+
+    pub fn new() -> Self {
+        let head = Node::new(None, None, Weak::new());
+        let tail = Node::new(None, None, Arc::downgrade(&head));
+        head.lock().next = Some(tail.clone());
+
+        ConcurrentGList {
+            head,
+            tail,
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    // Only a hint while other threads are busy with the list
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.lock();
+        Self::is_node(&head.next, &self.tail)
+    }
+
+    pub fn push_front(&self, elem: T) {
+        let mut head = self.head.lock();
+        let first = head.next.clone().unwrap();
+        let mut first_guard = first.lock();
+
+        let node = Node::new(Some(elem), Some(first.clone()), Arc::downgrade(&self.head));
+        first_guard.prev = Arc::downgrade(&node);
+        head.next = Some(node);
+        self.len.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn push_back(&self, elem: T) {
+        loop {
+            // Peek at who's last, then lock them in order and make sure
+            let Some(last) = self.tail.lock().prev.upgrade() else {
+                continue;
+            };
+            let mut last_guard = last.lock();
+            let mut tail = self.tail.lock();
+            if !last_guard.linked || !Self::is_node(&last_guard.next, &self.tail) {
+                continue;
+            }
+
+            let node = Node::new(Some(elem), Some(self.tail.clone()), Arc::downgrade(&last));
+            tail.prev = Arc::downgrade(&node);
+            last_guard.next = Some(node);
+            self.len.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+
+    pub fn pop_front(&self) -> Option<T> {
+        let mut head = self.head.lock();
+        let first = head.next.clone().unwrap();
+        if Arc::ptr_eq(&first, &self.tail) {
+            return None;
+        }
+
+        let mut first_guard = first.lock();
+        let second = first_guard.next.clone().unwrap();
+        let mut second_guard = second.lock();
+
+        second_guard.prev = Arc::downgrade(&self.head);
+        head.next = Some(second.clone());
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Self::unlinked(&mut first_guard)
+    }
+
+    pub fn pop_back(&self) -> Option<T> {
+        loop {
+            let Some(last) = self.tail.lock().prev.upgrade() else {
+                continue;
+            };
+            if Arc::ptr_eq(&last, &self.head) {
+                return None;
+            }
+            let Some(before) = last.lock().prev.upgrade() else {
+                continue;
+            };
+
+            let mut before_guard = before.lock();
+            let mut last_guard = last.lock();
+            let mut tail = self.tail.lock();
+            if !before_guard.linked
+                || !last_guard.linked
+                || !Self::is_node(&before_guard.next, &last)
+                || !Self::is_node(&last_guard.next, &self.tail)
+            {
+                continue;
+            }
+
+            tail.prev = Arc::downgrade(&before);
+            before_guard.next = Some(self.tail.clone());
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            return Self::unlinked(&mut last_guard);
+        }
+    }
+
+    // Sits in front of the first element, move_next() to get onto it
+    pub fn cursor(&self) -> CursorMut<'_, T> {
+        let node = self.head.clone();
+        CursorMut {
+            guard: unsafe { Self::lock_held(&node) },
+            node,
+            list: self,
+        }
+    }
+
+    // index == len is a push_back, past it panics
+    pub fn insert(&self, index: usize, elem: T) {
+        let mut cursor = self.cursor();
+        for _ in 0..index {
+            assert!(cursor.move_next(), "Cannot insert at a nonexistent index");
+        }
+        cursor.insert_after(elem);
+    }
+
+    pub fn remove(&self, index: usize) -> Option<T> {
+        let mut cursor = self.cursor();
+        for _ in 0..index {
+            if !cursor.move_next() {
+                return None;
+            }
+        }
+        cursor.remove_next()
+    }
+
+    pub fn snapshot(&self) -> Snapshot<T>
+    where
+        T: Clone,
+    {
+        let mut elems = Vec::with_capacity(self.len());
+        self.for_each(|elem| elems.push(elem.clone()));
+        Snapshot {
+            inner: elems.into_iter(),
+        }
+    }
+
+    // One hand over hand walk, front to back
+    pub fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        let mut cursor = self.cursor();
+        while cursor.move_next() {
+            if let Some(elem) = cursor.current() {
+                f(elem);
+            }
+        }
+    }
+
+    fn is_node(link: &Option<Arc<Node<T>>>, node: &Arc<Node<T>>) -> bool {
+        link.as_ref().is_some_and(|link| Arc::ptr_eq(link, node))
+    }
+
+    // Marks a node as gone and hands back its elem
+    fn unlinked(state: &mut State<T>) -> Option<T> {
+        state.linked = false;
+        state.next = None;
+        state.prev = Weak::new();
+        state.elem.take()
+    }
+
+    // Locks node with a guard that claims to live for 'a. Whoever holds it
+    // has to keep an Arc to node alive for longer than the guard.
+    unsafe fn lock_held<'a>(node: &Arc<Node<T>>) -> MutexGuard<'a, State<T>> {
+        mem::transmute::<MutexGuard<'_, State<T>>, MutexGuard<'a, State<T>>>(node.lock())
+    }
+}
+
+impl<T> Default for ConcurrentGList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for ConcurrentGList<T> {
+    fn drop(&mut self) {
+        // Unhook one node at a time so dropping a long list doesn't recurse
+        let mut cur = self.head.lock().next.take();
+        while let Some(node) = cur {
+            cur = node.lock().next.take();
+        }
+    }
+}
+
+impl<T> Extend<T> for ConcurrentGList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for ConcurrentGList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = ConcurrentGList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug> Debug for ConcurrentGList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        self.for_each(|elem| {
+            list.entry(elem);
+        });
+        list.finish()
+    }
+}
+
+impl<'a, T> CursorMut<'a, T> {
+
+    // Hand over hand onto the next element. Returns false (and stays put)
+    // if there isn't one.
+    pub fn move_next(&mut self) -> bool {
+        let next = self.guard.next.clone().unwrap();
+        if Arc::ptr_eq(&next, &self.list.tail) {
+            return false;
+        }
+
+        // Lock the next one before letting go of this one
+        let next_guard = unsafe { ConcurrentGList::lock_held(&next) };
+        drop(mem::replace(&mut self.guard, next_guard));
+        self.node = next;
+        true
+    }
+
+    // None while we're still in front of the first element
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.guard.elem.as_mut()
+    }
+
+    pub fn peek_next(&self) -> bool {
+        !ConcurrentGList::is_node(&self.guard.next, &self.list.tail)
+    }
+
+    // Puts elem right after the cursor, which stays where it is
+    pub fn insert_after(&mut self, elem: T) {
+        let next = self.guard.next.clone().unwrap();
+        let mut next_guard = next.lock();
+
+        let node = Node::new(Some(elem), Some(next.clone()), Arc::downgrade(&self.node));
+        next_guard.prev = Arc::downgrade(&node);
+        self.guard.next = Some(node);
+        self.list.len.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Takes out the element right after the cursor. There's no
+    // remove_current(), that would mean locking the node in front of us.
+    pub fn remove_next(&mut self) -> Option<T> {
+        let next = self.guard.next.clone().unwrap();
+        if Arc::ptr_eq(&next, &self.list.tail) {
+            return None;
+        }
+
+        let mut next_guard = next.lock();
+        let after = next_guard.next.clone().unwrap();
+        let mut after_guard = after.lock();
+
+        after_guard.prev = Arc::downgrade(&self.node);
+        self.guard.next = Some(after.clone());
+        self.list.len.fetch_sub(1, Ordering::Relaxed);
+        ConcurrentGList::unlinked(&mut next_guard)
+    }
+}
+
+impl<T> Iterator for Snapshot<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Snapshot<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for Snapshot<T> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::ConcurrentGList;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn contents(list: &ConcurrentGList<u32>) -> Vec<u32> {
+        list.snapshot().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let list = ConcurrentGList::new();
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+
+        list.push_back(2);
+        list.push_back(3);
+        list.push_front(1);
+        list.push_front(0);
+        assert_eq!(contents(&list), vec![0, 1, 2, 3]);
+        assert_eq!(list.len(), 4);
+
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.pop_back(), None);
+        assert!(list.is_empty());
+
+        list.push_front(7);
+        assert_eq!(list.pop_back(), Some(7));
+        assert_eq!(format!("{:?}", list), "[]");
+    }
+
+    #[test]
+    fn cursor_and_positions() {
+        let list: ConcurrentGList<u32> = (0..5).collect();
+        list.insert(0, 100);
+        list.insert(3, 200);
+        list.insert(7, 300);
+        assert_eq!(contents(&list), vec![100, 0, 1, 200, 2, 3, 4, 300]);
+
+        assert_eq!(list.remove(3), Some(200));
+        assert_eq!(list.remove(0), Some(100));
+        assert_eq!(list.remove(6), None);
+        assert_eq!(list.remove(5), Some(300));
+        assert_eq!(format!("{:?}", list), "[0, 1, 2, 3, 4]");
+
+        {
+            let mut cursor = list.cursor();
+            assert_eq!(cursor.current(), None);
+            while cursor.move_next() {
+                *cursor.current().unwrap() *= 10;
+            }
+            assert!(!cursor.peek_next());
+            assert_eq!(cursor.current(), Some(&mut 40));
+            cursor.insert_after(50);
+            assert_eq!(cursor.remove_next(), Some(50));
+            assert_eq!(cursor.remove_next(), None);
+        }
+        assert_eq!(contents(&list), vec![0, 10, 20, 30, 40]);
+    }
+
+    #[test]
+    #[should_panic(expected = "nonexistent index")]
+    fn insert_past_end() {
+        let list: ConcurrentGList<u32> = (0..3).collect();
+        list.insert(4, 0);
+    }
+
+    #[test]
+    fn both_ends_at_once() {
+        const THREADS: u32 = 8;
+        const PER_THREAD: u32 = 5_000;
+
+        let list = ConcurrentGList::new();
+        let mut popped: Vec<u32> = thread::scope(|s| {
+            let workers: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let list = &list;
+                    s.spawn(move || {
+                        let mut mine = Vec::new();
+                        for i in 0..PER_THREAD {
+                            let value = t * PER_THREAD + i;
+                            if i % 2 == 0 {
+                                list.push_back(value);
+                            } else {
+                                list.push_front(value);
+                            }
+                            if i % 3 == 0 {
+                                mine.extend(list.pop_back());
+                            }
+                            if i % 3 == 1 {
+                                mine.extend(list.pop_front());
+                            }
+                        }
+                        mine
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        });
+
+        assert_eq!(list.len() + popped.len(), (THREADS * PER_THREAD) as usize);
+        popped.extend(list.snapshot());
+        popped.sort_unstable();
+        assert!(popped.into_iter().eq(0..THREADS * PER_THREAD));
+    }
+
+    #[test]
+    fn inserts_in_different_places() {
+        // Every thread inserts right after its own marker while others
+        // hammer the ends, each thread's run has to come out contiguous
+        const THREADS: u32 = 4;
+        const PER_THREAD: u32 = 500;
+
+        let list = ConcurrentGList::new();
+        for t in 0..THREADS {
+            list.push_back(t * 1_000_000);
+        }
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let list = &list;
+                s.spawn(move || {
+                    for i in 1..=PER_THREAD {
+                        let mut cursor = list.cursor();
+                        while cursor.move_next() {
+                            if *cursor.current().unwrap() == t * 1_000_000 {
+                                break;
+                            }
+                        }
+                        // Newest right after the marker, so the run ends up backwards
+                        cursor.insert_after(t * 1_000_000 + i);
+                    }
+                });
+            }
+            s.spawn(|| {
+                for i in 0..PER_THREAD {
+                    list.push_back(u32::MAX - i);
+                    list.pop_back();
+                }
+            });
+        });
+
+        let all: Vec<u32> = list.snapshot().collect();
+        assert_eq!(all.len(), (THREADS * (PER_THREAD + 1)) as usize);
+        for (t, run) in all.chunks((PER_THREAD + 1) as usize).enumerate() {
+            let base = t as u32 * 1_000_000;
+            let expected: Vec<u32> = std::iter::once(base)
+                .chain((1..=PER_THREAD).rev().map(|i| base + i))
+                .collect();
+            assert_eq!(run, &expected[..]);
+        }
+    }
+
+    #[test]
+    fn snapshot_while_busy() {
+        let list: ConcurrentGList<u32> = (0..1000).collect();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..2000 {
+                    list.push_front(1000 + i);
+                    list.pop_back();
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..50 {
+                    // Whatever we see, it's in order: new stuff counts down
+                    // at the front, old stuff counts up at the back
+                    let seen: Vec<u32> = list.snapshot().collect();
+                    let split = seen.iter().position(|&x| x < 1000).unwrap_or(seen.len());
+                    assert!(seen[..split].windows(2).all(|w| w[0] > w[1]));
+                    assert!(seen[split..].windows(2).all(|w| w[0] < w[1]));
+                }
+            });
+        });
+        assert_eq!(list.len(), 1000);
+    }
+
+    #[test]
+    fn drops_everything() {
+        let tracker = Arc::new(());
+        {
+            let list = ConcurrentGList::new();
+            for _ in 0..100_000 {
+                list.push_back(tracker.clone());
+            }
+            list.pop_front();
+            list.remove(500);
+            assert_eq!(Arc::strong_count(&tracker), 99_999);
+        }
+        // Long list, nothing overflowed the stack on the way out either
+        assert_eq!(Arc::strong_count(&tracker), 1);
+    }
+}
@@ -4,16 +4,64 @@ use std::hash::Hasher;
 use std::hash::Hash;
 // Actually Wizard Magic!
 use std::ptr::NonNull;
+use std::mem;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
 use std::fmt;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 // Also Wizardry
 use std::marker::PhantomData;
 
-
+// Lists only take an id once they hand out a handle, 0 means "none yet"
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+
+/// A doubly linked deque.
+///
+/// It's Send/Sync whenever T is, same as std's LinkedList,
+/// so anything that isn't Send still can't leave its thread:
+///
+/// ```compile_fail
+/// use gangsterlist::gangsterlist::GList;
+/// use std::rc::Rc;
+///
+/// let mut list = GList::new();
+/// list.push_back(Rc::new(5));
+/// std::thread::spawn(move || drop(list));
+/// ```
+///
+/// ```compile_fail
+/// use gangsterlist::gangsterlist::GList;
+/// use std::cell::Cell;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let list: GList<Cell<u32>> = GList::new();
+/// assert_sync(&list);
+/// ```
+///
+/// ```compile_fail
+/// use gangsterlist::gangsterlist::GList;
+/// use std::cell::Cell;
+///
+/// // Handing out &T across threads needs T: Sync
+/// fn assert_send<T: Send>(_: T) {}
+/// let list: GList<Cell<u32>> = GList::new();
+/// assert_send(list.iter());
+/// ```
 pub struct GList<T> {
     front: Link<T>,
     back: Link<T>,
     len: usize,
+    // Stamped into every handle we give out, 0 until we've given one out
+    id: u64,
+    // Where our handles point. Indexing in here and checking the generation
+    // is the whole lookup, and a stale handle never touches node memory.
+    handles: Vec<HandleSlot<T>>,
+    free_handles: Vec<usize>,
+    // Popped nodes we hang onto for the next push, off unless asked for
+    pool: NodePool<T>,
     // Semantically Store Values of T by value
     _boo: PhantomData<T>,
 }
@@ -38,14 +86,91 @@ pub struct IntoIter<T> {
     list: GList<T>,
 }
 
+// A cursor sits on an element, or on the "ghost" between back and front.
+// index is None exactly when cur is None (i.e. we're on the ghost)
+pub struct CursorMut<'a, T> {
+    list: &'a mut GList<T>,
+    cur: Link<T>,
+    index: Option<usize>,
+}
+
+pub struct Drain<'a, T> {
+    // The range is already cut out, we just hold onto the borrow
+    _list: PhantomData<&'a mut GList<T>>,
+    iter: IntoIter<T>,
+}
+
+pub struct ExtractIf<'a, T, F>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    list: &'a mut GList<T>,
+    // The next node the predicate gets to look at
+    next: Link<T>,
+    pred: F,
+}
+
+// Same idea as CursorMut but read only, so it's cheap to copy around
+pub struct Cursor<'a, T> {
+    list: &'a GList<T>,
+    cur: Link<T>,
+    index: Option<usize>,
+}
+
 type Link<T> = Option<NonNull<Node<T>>>;
 
 struct Node<T> {
     front: Link<T>,
     back: Link<T>,
+    // Our handle slot, if we've got one. Only trust it if the slot points
+    // back at us, nodes don't get told when they move lists.
+    slot: usize,
     elem: T,
 }
 
+const NO_SLOT: usize = usize::MAX;
+
+// A spot in a list's handle table. Vacated whenever its node is removed
+// or moves to another list.
+struct HandleSlot<T> {
+    node: Link<T>,
+    // Bumped on every vacate, so handles from before stop matching
+    generation: u64,
+}
+
+// Remembers where an element lives so it can be found again in O(1).
+// Only good for the list it came from, and goes stale once the element is
+// removed or moved to another list (append/prepend_list/merge from the other
+// side, split_off, split_before/after, drain). Handles to everything that
+// stays put keep working through all of those.
+pub struct NodeHandle<T> {
+    owner: u64,
+    slot: usize,
+    generation: u64,
+    _boo: PhantomData<fn() -> T>,
+}
+
+// Spare node allocations, chained together through their back links.
+// Nodes in here have had their elem moved out, so never drop one as a Node!
+struct NodePool<T> {
+    free: Link<T>,
+    cached: usize,
+    max_cached: usize,
+    reused: usize,
+    allocated: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    // Spare nodes sitting in the pool right now
+    pub cached: usize,
+    pub max_cached: usize,
+    // Pushes that got a recycled node
+    pub reused: usize,
+    // Pushes that had to go to the allocator
+    pub allocated: usize,
+}
+
 
 impl<T> GList<T> {
     
@@ -54,10 +179,22 @@ impl<T> GList<T> {
             front: None,
             back: None,
             len: 0,
+            id: 0,
+            handles: Vec::new(),
+            free_handles: Vec::new(),
+            pool: NodePool::new(0),
             _boo: PhantomData,
         }
     }
 
+    // Keeps up to max_cached popped nodes around so pushes can reuse them
+    // instead of allocating. new() is the same thing with a pool of 0.
+    pub fn with_node_pool(max_cached: usize) -> Self {
+        let mut list = Self::new();
+        list.pool.max_cached = max_cached;
+        list
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
@@ -68,11 +205,7 @@ impl<T> GList<T> {
 
         unsafe {
 
-            let new = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
-                front: None,
-                back: None,
-                elem,
-            })));
+            let new = self.alloc_node(elem);
 
             if let Some(old) = self.front {
                 // Put new type in front of the old
@@ -87,16 +220,13 @@ impl<T> GList<T> {
 
         }
 
+        self.after_mutation();
     }
 
     pub fn push_back(&mut self, elem: T) {
         unsafe {
 
-            let new = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
-                front: None,
-                back: None,
-                elem
-            })));
+            let new = self.alloc_node(elem);
 
             if let Some(old) = self.back {
                 (*old.as_ptr()).back = Some(new);
@@ -109,6 +239,8 @@ impl<T> GList<T> {
             self.back = Some(new);
             self.len += 1;
         }
+
+        self.after_mutation();
     }
 
     pub fn pop_front(&mut self) -> Option<T> {
@@ -117,12 +249,8 @@ impl<T> GList<T> {
 
             // Only do work if we even have a front node to pop
             self.front.map(|node| {
-                // grab the boxed node so we can unbox the fuck out of it
-                let box_node = Box::from_raw(node.as_ptr());
-                let result = box_node.elem;
-
                 // Make the next node into the new front 
-                self.front = box_node.back;
+                self.front = (*node.as_ptr()).back;
 
                 if let Some(new) = self.front {
                     // Wipe clean its reference to the removed node
@@ -132,8 +260,10 @@ impl<T> GList<T> {
                 }
 
                 self.len -= 1; // deincremment list length
+                // unbox the fuck out of it (or stash it in the pool)
+                let result = self.free_node(node);
+                self.after_mutation();
                 result
-                // Box is implicitly freed here
             })
 
 
@@ -145,11 +275,8 @@ impl<T> GList<T> {
     pub fn pop_back(&mut self) -> Option<T> {
         unsafe {
             self.back.map(|node| {
-                let box_node = Box::from_raw(node.as_ptr());
-                let result = box_node.elem;
-
                 // make next node new back
-                self.back = box_node.front;
+                self.back = (*node.as_ptr()).front;
 
                 if let Some(new) = self.back {
                     // Clear its reference to the removed node
@@ -160,6 +287,8 @@ impl<T> GList<T> {
                 }
 
                 self.len -= 1; // deincrement list length
+                let result = self.free_node(node);
+                self.after_mutation();
                 result
             })
         }
@@ -212,527 +341,2899 @@ impl<T> GList<T> {
         IntoIter { list: self }
     }
 
-
-    // Helper Functions
-    pub fn is_empty(&self) -> bool {
-        self.len == 0
-    }
-    // Literally Just Drop.
-    // Which kinda means, we can make drop just call clear()
-    pub fn clear(&mut self) {
-        while self.pop_front().is_some() {}
+    // Starts on the ghost, so the first move_next() lands on the front
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            list: self,
+            cur: None,
+            index: None,
+        }
     }
 
-}
-
-
-// Jesus Christ
-impl<T> Default for GList<T> {
-    fn default() -> Self {
-        let list = Self {
-            front: None,
-            back: None,
-            len: 0,
-            _boo: PhantomData,
-        };
-        list
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.front,
+            index: self.front.map(|_| 0),
+            list: self,
+        }
     }
-}
 
-impl<T: Clone> Clone for GList<T> {
-    fn clone(&self) -> Self {
-        let mut new_list = Self::new();
-        for item in self {
-            new_list.push_back(item.clone());
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.back,
+            index: self.back.map(|_| self.len - 1),
+            list: self,
         }
-        new_list
     }
-}
 
-impl<T> Extend<T> for GList<T> {
-    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        for item in iter{
-            self.push_back(item);
+    // Read only versions of the above, same starting spots
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            list: self,
+            cur: None,
+            index: None,
         }
     }
-}
 
-impl<T: Debug> Debug for GList<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().entries(self).finish()
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor {
+            list: self,
+            cur: self.front,
+            index: self.front.map(|_| 0),
+        }
     }
-}
 
-impl<T: PartialEq> PartialEq for GList<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.len() == other.len() && self.iter().eq(other)
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        Cursor {
+            list: self,
+            cur: self.back,
+            index: self.back.map(|_| self.len - 1),
+        }
     }
 
-    fn ne(&self, other: &Self) -> bool {
-        self.len() != other.len() || self.iter().ne(other)
+    // Parks a cursor right on the handle's node. We can't work out the
+    // index without walking, so the caller has to already know it.
+    pub(crate) fn cursor_at(&self, handle: &NodeHandle<T>, index: usize) -> Option<Cursor<'_, T>> {
+        let cur = self.resolve(handle)?;
+        debug_assert!(index < self.len, "Cursor index {} is past the end ({})", index, self.len);
+        #[cfg(any(debug_assertions, feature = "debug-invariants"))]
+        self.check_index_of(cur, index);
+        Some(Cursor {
+            list: self,
+            cur: Some(cur),
+            index: Some(index),
+        })
     }
-}
 
-impl<T: Eq> Eq for GList<T> {}
-
-impl<T: PartialOrd> PartialOrd for GList<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.iter().partial_cmp(other)
+    pub(crate) fn cursor_mut_at(&mut self, handle: &NodeHandle<T>, index: usize) -> Option<CursorMut<'_, T>> {
+        let cur = self.resolve(handle)?;
+        debug_assert!(index < self.len, "Cursor index {} is past the end ({})", index, self.len);
+        #[cfg(any(debug_assertions, feature = "debug-invariants"))]
+        self.check_index_of(cur, index);
+        Some(CursorMut {
+            list: self,
+            cur: Some(cur),
+            index: Some(index),
+        })
     }
-}
 
-impl<T: Ord> Ord for GList<T> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.iter().cmp(other)
+    // A wrong index would throw off split_* and index() later on, so make
+    // it blow up here instead. Walks, so only in checked builds.
+    #[cfg(any(debug_assertions, feature = "debug-invariants"))]
+    fn check_index_of(&self, node: NonNull<Node<T>>, index: usize) {
+        assert!(self.node_at(index) == Some(node), "Handle's node isn't at index {}", index);
     }
-}
 
-impl<T: Hash> Hash for GList<T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.len().hash(state);
-        for item in self {
-            item.hash(state)
+
+    // Positional stuff, all of it walks in from whichever end is closer
+    pub fn get(&self, index: usize) -> Option<&T> {
+        unsafe {
+            self.node_at(index).map(|node| &(*node.as_ptr()).elem)
         }
     }
-}
-
-impl<'a, T> IntoIterator for &'a GList<T> {
-    type IntoIter = Iter<'a, T>;
-    type Item = &'a T;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        unsafe {
+            self.node_at(index).map(|node| &mut (*node.as_ptr()).elem)
+        }
     }
-}
 
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = &'a T;
+    // Like VecDeque, index == len is fine (that's a push_back) but past it panics
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len, "Cannot insert at a nonexistent index");
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.len > 0 {
-            self.front.map(|node| unsafe {
-                self.len -= 1;
-                self.front = (*node.as_ptr()).back;
-                &(*node.as_ptr()).elem
-            })
-        } else {
-            None
+        if index == self.len {
+            self.push_back(elem);
+            return;
         }
+
+        let cur = self.node_at(index);
+        let mut cursor = CursorMut {
+            list: self,
+            cur,
+            index: Some(index),
+        };
+        cursor.insert_before(elem);
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.len, Some(self.len))
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        unsafe {
+            self.node_at(index).map(|node| self.unlink_node(node))
+        }
     }
-}
 
-impl<'a, T> Iterator for IterMut<'a, T> {
-    type Item = &'a mut T;
+    // Moves the first k elements round to the back without reallocating
+    // anything. Like VecDeque, k > len panics.
+    pub fn rotate_left(&mut self, k: usize) {
+        assert!(k <= self.len, "Cannot rotate by more than the list's length");
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.len > 0 {
-            self.front.map(|node| unsafe {
-                self.len -= 1;
-                self.front = (*node.as_ptr()).back;
-                &mut (*node.as_ptr()).elem
-            })
-        } else {
-            None
+        if k == 0 || k == self.len {
+            return;
         }
-    }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.len, Some(self.len))
-    }
-}
+        // node_at walks in from the nearer end, so this is O(min(k, len - k))
+        let new_front = self.node_at(k).unwrap();
 
-impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+        unsafe {
+            let new_back = (*new_front.as_ptr()).front.unwrap();
+            let old_front = self.front.unwrap();
+            let old_back = self.back.unwrap();
 
-    fn next_back(&mut self) -> Option<Self::Item> {
-        if self.len > 0 {
-            self.back.map(|node| unsafe {
-                self.len -= 1;
-                self.back = (*node.as_ptr()).front;
-                &mut (*node.as_ptr()).elem
-            })
-        } else {
-            None
+            // Close the chain into a ring...
+            (*old_back.as_ptr()).back = Some(old_front);
+            (*old_front.as_ptr()).front = Some(old_back);
+
+            // ...and snip it open again at the new split point
+            (*new_back.as_ptr()).back = None;
+            (*new_front.as_ptr()).front = None;
+
+            self.front = Some(new_front);
+            self.back = Some(new_back);
         }
+
+        self.after_mutation();
     }
-}
 
-impl<'a, T> ExactSizeIterator for IterMut<'a, T> {
+    // Moves the last k elements round to the front
+    pub fn rotate_right(&mut self, k: usize) {
+        assert!(k <= self.len, "Cannot rotate by more than the list's length");
+        self.rotate_left(self.len - k);
+    }
 
-    fn len(&self) -> usize {
-        self.len
+    // Moves everything out of other onto our back, just relinks the ends
+    pub fn append(&mut self, other: &mut GList<T>) {
+        // splice_before on the ghost puts things on the back
+        self.cursor_mut().splice_before(other.take_all());
     }
 
-}
+    // Moves everything out of other onto our front
+    pub fn prepend_list(&mut self, other: &mut GList<T>) {
+        self.cursor_mut().splice_after(other.take_all());
+    }
 
-impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        if self.len > 0 {
-            self.back.map(|node| unsafe {
-                self.len -= 1;
-                self.back = (*node.as_ptr()).front;
-                &(*node.as_ptr()).elem
-            })
-        } else {
-            None
+    // Everything from index at onwards gets moved into a new list
+    pub fn split_off(&mut self, at: usize) -> GList<T> {
+        assert!(at <= self.len, "Cannot split off at a nonexistent index");
+
+        if at == 0 {
+            return self.take_all();
         }
-    }
-}
 
-impl<'a, T> ExactSizeIterator for Iter<'a, T> {
-    fn len(&self) -> usize {
-        self.len
+        // Sit a cursor on the last node we keep and cut behind it
+        let cur = self.node_at(at - 1);
+        let mut cursor = CursorMut {
+            list: self,
+            cur,
+            index: Some(at - 1),
+        };
+        cursor.split_after()
     }
-}
 
+    // Unlinks the range and yields it by value. The list is stitched
+    // back together when the Drain goes away, anything it didn't yield
+    // gets dropped along with it.
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, T>
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1).expect("Drain range start overflowed"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1).expect("Drain range end overflowed"),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+        assert!(start <= end, "Drain range starts after it ends");
+        assert!(end <= self.len, "Drain range ends past the end of the list");
 
-impl<T> IntoIterator for GList<T> {
-    type IntoIter = IntoIter<T>;
-    type Item = T;
-    fn into_iter(self) -> Self::IntoIter {
-        self.into_iter()
+        Drain {
+            iter: self.cut_range(start, end).into_iter(),
+            _list: PhantomData,
+        }
     }
-}
 
-impl<T> Iterator for IntoIter<T> {
-    type Item = T;
+    // Unlinks [start..end] as a list of its own and stitches us back together
+    // over the hole. Only the nodes that leave lose their handles.
+    fn cut_range(&mut self, start: usize, end: usize) -> GList<T> {
+        let mut output = GList::new();
+        if start == end {
+            return output;
+        }
+        let first = self.node_at(start).unwrap();
+        let last = self.node_at(end - 1).unwrap();
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.list.pop_front()
+        unsafe {
+            let prev = (*first.as_ptr()).front.take();
+            let next = (*last.as_ptr()).back.take();
+            match prev {
+                Some(prev) => (*prev.as_ptr()).back = next,
+                None => self.front = next,
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).front = prev,
+                None => self.back = prev,
+            }
+            self.len -= end - start;
+            self.forget_handles_in(Some(first));
+        }
+
+        output.front = Some(first);
+        output.back = Some(last);
+        output.len = end - start;
+        self.after_mutation();
+        output.after_mutation();
+        output
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.list.len, Some(self.list.len))
+    // Stable merge sort that only rewires links, nothing gets moved or allocated
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(|a, b| a.cmp(b));
     }
-}
 
-impl<T> DoubleEndedIterator for IntoIter<T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.list.pop_back()
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
     }
-}
 
-impl<T> ExactSizeIterator for IntoIter<T> {
-    fn len(&self) -> usize {
-        self.list.len
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        if self.len < 2 {
+            return;
+        }
+
+        // Bottom up: merge runs of 1, then 2, then 4... until one pass
+        // only has a single merge to do
+        let mut sorter = MergeSort::new(self);
+        let mut width = 1;
+        while sorter.merge_pass(width, width, &mut compare) > 1 {
+            width *= 2;
+        }
     }
-}
 
-impl<T> FromIterator<T> for GList<T> {
-    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut list = Self::new();
-        list.extend(iter);
-        list
+    pub fn is_sorted(&self) -> bool
+    where
+        T: PartialOrd,
+    {
+        self.iter().zip(self.iter().skip(1)).all(|(a, b)| a <= b)
     }
-}
 
+    // Merges a sorted other into sorted self, other is left empty.
+    // Stable, so on ties our elements stay in front of theirs.
+    pub fn merge(&mut self, other: &mut GList<T>)
+    where
+        T: Ord,
+    {
+        let ours = self.len;
+        let theirs = other.len;
+        self.append(other);
+
+        if ours > 0 && theirs > 0 {
+            MergeSort::new(self).merge_pass(ours, theirs, &mut |a: &T, b: &T| a.cmp(b));
+        }
+    }
 
-// Implement Drop
-impl<T> Drop for GList<T> {
+    // Keeps only the elements f says yes to, survivors aren't reallocated
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.retain_mut(|elem| f(elem));
+    }
 
-    /* In case i fuck this up and im totally wrong 
-    fn drop(&mut self) {
-        // Pop Till We Die
-        while let Some(_) = self.pop_front() {}
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        for _ in self.extract_if(|elem| !f(elem)) {}
     }
-    */
 
-    fn drop(&mut self) {
-        self.clear();
+    // Lazily unlinks and yields every element pred says yes to.
+    // Whatever we haven't looked at yet stays put if this is dropped early.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, T, F>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        ExtractIf {
+            next: self.front,
+            list: self,
+            pred,
+        }
     }
 
-}
+    // Same as push_front, but hands back a handle to the new element
+    pub fn push_front_handle(&mut self, elem: T) -> NodeHandle<T> {
+        self.push_front(elem);
+        unsafe { self.make_handle(self.front.unwrap()) }
+    }
 
+    pub fn push_back_handle(&mut self, elem: T) -> NodeHandle<T> {
+        self.push_back(elem);
+        unsafe { self.make_handle(self.back.unwrap()) }
+    }
 
+    pub fn get_by_handle(&self, handle: &NodeHandle<T>) -> Option<&T> {
+        unsafe {
+            self.resolve(handle).map(|node| &(*node.as_ptr()).elem)
+        }
+    }
 
+    pub fn get_by_handle_mut(&mut self, handle: &NodeHandle<T>) -> Option<&mut T> {
+        unsafe {
+            self.resolve(handle).map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
 
-#[cfg(test)]
-mod test {
+    // None if the handle is stale or belongs to some other list
+    pub fn remove_handle(&mut self, handle: &NodeHandle<T>) -> Option<T> {
+        unsafe {
+            self.resolve(handle).map(|node| self.unlink_node(node))
+        }
+    }
 
-    use super::GList;
+    // Relinks the node at the front without reallocating it.
+    // Returns false if the handle is no good.
+    pub fn move_to_front(&mut self, handle: &NodeHandle<T>) -> bool {
+        let Some(node) = self.resolve(handle) else {
+            return false;
+        };
 
+        unsafe {
+            self.detach_node(node);
+            self.attach_front(node);
+        }
+        self.after_mutation();
+        true
+    }
 
+    pub fn move_to_back(&mut self, handle: &NodeHandle<T>) -> bool {
+        let Some(node) = self.resolve(handle) else {
+            return false;
+        };
 
-    fn generate_test() -> GList<i32> {
-        list_from(&[0, 1, 2, 3, 4, 5, 6])
+        unsafe {
+            self.detach_node(node);
+            self.attach_back(node);
+        }
+        self.after_mutation();
+        true
     }
 
-    fn list_from<T: Clone>(v: &[T]) -> GList<T> {
-        v.iter().map(|x| (*x).clone()).collect()
+    // Only trust a handle we gave out ourselves, whose slot hasn't been
+    // vacated since. Never looks at the node unless it's still ours.
+    fn resolve(&self, handle: &NodeHandle<T>) -> Link<T> {
+        if handle.owner != self.id {
+            return None;
+        }
+        match self.handles.get(handle.slot) {
+            Some(slot) if slot.generation == handle.generation => slot.node,
+            _ => None,
+        }
     }
 
+    // Hands back the node's slot if it already has one, asking twice gets
+    // the same handle
+    unsafe fn make_handle(&mut self, node: NonNull<Node<T>>) -> NodeHandle<T> {
+        if self.id == 0 {
+            self.id = NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        }
 
-    #[test]
-    fn test_basic_front() {
-        let mut list = GList::new();
-
-        // Try to break an empty list
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
-
-        // Try to break a one item list
-        list.push_front(10);
-        assert_eq!(list.len(), 1);
-        assert_eq!(list.pop_front(), Some(10));
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
-
-        // Mess around
-        list.push_front(10);
-        assert_eq!(list.len(), 1);
-        list.push_front(20);
-        assert_eq!(list.len(), 2);
-        list.push_front(30);
-        assert_eq!(list.len(), 3);
-        assert_eq!(list.pop_front(), Some(30));
-        assert_eq!(list.len(), 2);
-        list.push_front(40);
-        assert_eq!(list.len(), 3);
-        assert_eq!(list.pop_front(), Some(40));
-        assert_eq!(list.len(), 2);
-        assert_eq!(list.pop_front(), Some(20));
-        assert_eq!(list.len(), 1);
-        assert_eq!(list.pop_front(), Some(10));
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
-
-    }
-
-
-    #[test]
-    fn test_basic() {
-        let mut m = GList::new();
-
-        assert_eq!(m.pop_front(), None);
-        assert_eq!(m.pop_back(), None);
-        assert_eq!(m.pop_front(), None);
-        m.push_front(1);
-        assert_eq!(m.pop_front(), Some(1));
-        m.push_back(2);
-        m.push_back(3);
-        assert_eq!(m.len(), 2);
-        assert_eq!(m.pop_front(), Some(2));
-        assert_eq!(m.pop_front(), Some(3));
-        assert_eq!(m.len(), 0);
-        assert_eq!(m.pop_front(), None);
-        m.push_back(1);
-        m.push_back(3);
-        m.push_back(5);
-        m.push_back(7);
-        assert_eq!(m.pop_front(), Some(1));
+        let mut slot = (*node.as_ptr()).slot;
+        if !self.owns_slot(node, slot) {
+            slot = match self.free_handles.pop() {
+                Some(slot) => {
+                    self.handles[slot].node = Some(node);
+                    slot
+                }
+                None => {
+                    self.handles.push(HandleSlot { node: Some(node), generation: 0 });
+                    self.handles.len() - 1
+                }
+            };
+            (*node.as_ptr()).slot = slot;
+        }
+
+        NodeHandle {
+            owner: self.id,
+            slot,
+            generation: self.handles[slot].generation,
+            _boo: PhantomData,
+        }
+    }
+
+    // node's slot field might be left over from another list,
+    // so it only counts if our slot points back at it
+    fn owns_slot(&self, node: NonNull<Node<T>>, slot: usize) -> bool {
+        matches!(self.handles.get(slot), Some(entry) if entry.node == Some(node))
+    }
+
+    // Makes any handle to node stale, node is leaving us one way or another
+    unsafe fn forget_handle(&mut self, node: NonNull<Node<T>>) {
+        let slot = (*node.as_ptr()).slot;
+        if self.owns_slot(node, slot) {
+            let entry = &mut self.handles[slot];
+            entry.node = None;
+            entry.generation += 1;
+            self.free_handles.push(slot);
+        }
+    }
+
+    // Same for a whole chain that just got cut loose from us. Only walks it
+    // if we've got any handles out at all.
+    unsafe fn forget_handles_in(&mut self, front: Link<T>) {
+        let mut cur = front;
+        while let Some(node) = cur {
+            if self.handles.len() == self.free_handles.len() {
+                return;
+            }
+            self.forget_handle(node);
+            cur = (*node.as_ptr()).back;
+        }
+    }
+
+    // Cuts node out of the chain and frees it, handing back its elem.
+    // node has to actually be in this list!
+    unsafe fn unlink_node(&mut self, node: NonNull<Node<T>>) -> T {
+        self.detach_node(node);
+        let elem = self.free_node(node);
+        self.after_mutation();
+        elem
+    }
+
+    // Every node we make comes through here, recycled if the pool has one
+    fn alloc_node(&mut self, elem: T) -> NonNull<Node<T>> {
+        let node = Node {
+            front: None,
+            back: None,
+            slot: NO_SLOT,
+            elem,
+        };
+
+        unsafe {
+            if let Some(spare) = self.pool.free {
+                self.pool.free = (*spare.as_ptr()).back;
+                self.pool.cached -= 1;
+                self.pool.reused += 1;
+                // The old elem is long gone, so write over it without dropping
+                spare.as_ptr().write(node);
+                spare
+            } else {
+                self.pool.allocated += 1;
+                NonNull::new_unchecked(Box::into_raw(Box::new(node)))
+            }
+        }
+    }
+
+    // Every node we get rid of goes through here. Moves elem out and
+    // either stashes the allocation in the pool or frees it.
+    // node must already be unlinked from the list!
+    unsafe fn free_node(&mut self, node: NonNull<Node<T>>) -> T {
+        self.forget_handle(node);
+        let elem = ptr::read(&(*node.as_ptr()).elem);
+
+        if self.pool.cached < self.pool.max_cached {
+            (*node.as_ptr()).back = self.pool.free;
+            self.pool.free = Some(node);
+            self.pool.cached += 1;
+        } else {
+            NodePool::release(node);
+        }
+
+        elem
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            cached: self.pool.cached,
+            max_cached: self.pool.max_cached,
+            reused: self.pool.reused,
+            allocated: self.pool.allocated,
+        }
+    }
+
+    // Gives every spare node in the pool back to the allocator.
+    // The pool keeps its limit and will fill up again as things get popped.
+    pub fn shrink_pool(&mut self) {
+        unsafe {
+            while let Some(spare) = self.pool.free {
+                self.pool.free = (*spare.as_ptr()).back;
+                NodePool::release(spare);
+            }
+        }
+        self.pool.cached = 0;
+        self.after_mutation();
+    }
+
+    // Steals our whole chain into a new list, our pool stays right where it is.
+    // Every handle we gave out goes stale, so just empty the whole table.
+    fn take_all(&mut self) -> GList<T> {
+        for (slot, entry) in self.handles.iter_mut().enumerate() {
+            if entry.node.take().is_some() {
+                entry.generation += 1;
+                self.free_handles.push(slot);
+            }
+        }
+
+        GList {
+            front: self.front.take(),
+            back: self.back.take(),
+            len: mem::replace(&mut self.len, 0),
+            id: 0,
+            handles: Vec::new(),
+            free_handles: Vec::new(),
+            pool: NodePool::new(0),
+            _boo: PhantomData,
+        }
+    }
+
+    // Cuts node out of the chain but leaves it allocated
+    unsafe fn detach_node(&mut self, node: NonNull<Node<T>>) {
+        let prev = (*node.as_ptr()).front.take();
+        let next = (*node.as_ptr()).back.take();
+
+        // Stitch the neighbours (or the list ends) together over the hole
+        match prev {
+            Some(prev) => (*prev.as_ptr()).back = next,
+            None => self.front = next,
+        }
+        match next {
+            Some(next) => (*next.as_ptr()).front = prev,
+            None => self.back = prev,
+        }
+
+        self.len -= 1;
+    }
+
+    // Links a detached node in at the front
+    unsafe fn attach_front(&mut self, node: NonNull<Node<T>>) {
+        (*node.as_ptr()).front = None;
+        (*node.as_ptr()).back = self.front;
+
+        match self.front {
+            Some(old) => (*old.as_ptr()).front = Some(node),
+            None => self.back = Some(node),
+        }
+
+        self.front = Some(node);
+        self.len += 1;
+    }
+
+    unsafe fn attach_back(&mut self, node: NonNull<Node<T>>) {
+        (*node.as_ptr()).back = None;
+        (*node.as_ptr()).front = self.back;
+
+        match self.back {
+            Some(old) => (*old.as_ptr()).back = Some(node),
+            None => self.front = Some(node),
+        }
+
+        self.back = Some(node);
+        self.len += 1;
+    }
+
+    // Walks the whole chain both ways and panics with a list of everything
+    // that's wrong if the links, len, handles or pool don't add up.
+    // Only exists in debug builds or with the debug-invariants feature.
+    #[cfg(any(debug_assertions, test, feature = "debug-invariants"))]
+    pub fn check_invariants(&self) {
+        use std::collections::HashSet;
+
+        let mut problems = Vec::new();
+        let mut seen = HashSet::new();
+
+        unsafe {
+            match (self.front, self.back) {
+                (None, None) => {}
+                (Some(front), Some(back)) => {
+                    if (*front.as_ptr()).front.is_some() {
+                        problems.push("front node has something in front of it".to_string());
+                    }
+                    if (*back.as_ptr()).back.is_some() {
+                        problems.push("back node has something behind it".to_string());
+                    }
+                }
+                (front, back) => problems.push(format!(
+                    "only one end is set (front: {:?}, back: {:?})",
+                    front, back
+                )),
+            }
+
+            // Front to back, checking every node points back at the one before it.
+            // Give up after len + 1 nodes in case the chain loops.
+            let mut prev: Link<T> = None;
+            let mut cur = self.front;
+            let mut count = 0;
+            while let Some(node) = cur {
+                if count > self.len {
+                    problems.push(format!("forward walk went past len ({}), chain probably loops", self.len));
+                    break;
+                }
+                if (*node.as_ptr()).front != prev {
+                    problems.push(format!("node {} front link doesn't point at node {}", count, count as isize - 1));
+                }
+                seen.insert(node);
+                prev = Some(node);
+                cur = (*node.as_ptr()).back;
+                count += 1;
+            }
+            if prev != self.back {
+                problems.push("forward walk didn't end on the back node".to_string());
+            }
+            if count != self.len {
+                problems.push(format!("counted {} nodes front to back but len is {}", count, self.len));
+            }
+
+            let mut next: Link<T> = None;
+            let mut cur = self.back;
+            let mut count = 0;
+            while let Some(node) = cur {
+                if count > self.len {
+                    problems.push(format!("backward walk went past len ({}), chain probably loops", self.len));
+                    break;
+                }
+                if (*node.as_ptr()).back != next {
+                    problems.push(format!("node {} from the back has a bad back link", count));
+                }
+                next = Some(node);
+                cur = (*node.as_ptr()).front;
+                count += 1;
+            }
+            if next != self.front {
+                problems.push("backward walk didn't end on the front node".to_string());
+            }
+            if count != self.len {
+                problems.push(format!("counted {} nodes back to front but len is {}", count, self.len));
+            }
+
+            // Every slot in use points at one of our nodes, which points back
+            let mut vacant = 0;
+            for (slot, entry) in self.handles.iter().enumerate() {
+                match entry.node {
+                    Some(node) if !seen.contains(&node) => {
+                        problems.push(format!("handle slot {} points at a node that isn't in the list", slot));
+                    }
+                    Some(node) if (*node.as_ptr()).slot != slot => {
+                        problems.push(format!("handle slot {} points at a node that thinks it's in slot {}", slot, (*node.as_ptr()).slot));
+                    }
+                    Some(_) => {}
+                    None => vacant += 1,
+                }
+            }
+            if vacant != self.free_handles.len()
+                || self.free_handles.iter().any(|&slot| self.handles[slot].node.is_some())
+            {
+                problems.push(format!(
+                    "{} handle slots are vacant but {} are on the free list",
+                    vacant,
+                    self.free_handles.len()
+                ));
+            }
+
+            let mut spares = 0;
+            let mut cur = self.pool.free;
+            while let Some(node) = cur {
+                if spares > self.pool.cached {
+                    break;
+                }
+                if seen.contains(&node) {
+                    problems.push("a node is in both the list and the pool".to_string());
+                }
+                spares += 1;
+                cur = (*node.as_ptr()).back;
+            }
+            if spares != self.pool.cached || spares > self.pool.max_cached {
+                problems.push(format!(
+                    "pool holds {} spares but thinks it has {} (max {})",
+                    spares, self.pool.cached, self.pool.max_cached
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            panic!("GList invariants broken:\n  - {}", problems.join("\n  - "));
+        }
+    }
+
+    // Every mutation ends with this. Checks everything in test builds and
+    // under the debug-invariants feature, compiles to nothing otherwise.
+    // Skipped while unwinding, so a Drop during a panic doesn't turn it into an abort.
+    #[inline]
+    fn after_mutation(&self) {
+        #[cfg(any(test, feature = "debug-invariants"))]
+        if !std::thread::panicking() {
+            self.check_invariants();
+        }
+    }
+
+    // Walks from whichever end is closer to index
+    fn node_at(&self, index: usize) -> Link<T> {
+        if index >= self.len {
+            return None;
+        }
+
+        unsafe {
+            if index < self.len / 2 {
+                let mut node = self.front;
+                for _ in 0..index {
+                    node = (*node?.as_ptr()).back;
+                }
+                node
+            } else {
+                let mut node = self.back;
+                for _ in index + 1..self.len {
+                    node = (*node?.as_ptr()).front;
+                }
+                node
+            }
+        }
+    }
+
+    // Helper Functions
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    // Literally Just Drop.
+    // Which kinda means, we can make drop just call clear()
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+}
+
+
+// NonNull opts us out of Send and Sync, but we own our Ts just like a Box
+// would, so put back the same bounds std's LinkedList has.
+unsafe impl<T: Send> Send for GList<T> {}
+unsafe impl<T: Sync> Sync for GList<T> {}
+
+unsafe impl<'a, T: Sync> Send for Iter<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Iter<'a, T> {}
+
+unsafe impl<'a, T: Send> Send for IterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for IterMut<'a, T> {}
+
+unsafe impl<'a, T: Sync> Send for Cursor<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Cursor<'a, T> {}
+
+unsafe impl<'a, T: Send> Send for CursorMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for CursorMut<'a, T> {}
+
+
+
+// Jesus Christ
+impl<T> Default for GList<T> {
+    fn default() -> Self {
+        let list = Self {
+            front: None,
+            back: None,
+            len: 0,
+            id: 0,
+            handles: Vec::new(),
+            free_handles: Vec::new(),
+            pool: NodePool::new(0),
+            _boo: PhantomData,
+        };
+        list
+    }
+}
+
+impl<T: Clone> Clone for GList<T> {
+    fn clone(&self) -> Self {
+        let mut new_list = Self::new();
+        for item in self {
+            new_list.push_back(item.clone());
+        }
+        new_list
+    }
+}
+
+impl<T> Extend<T> for GList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter{
+            self.push_back(item);
+        }
+    }
+}
+
+impl<T: Debug> Debug for GList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for GList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+
+    fn ne(&self, other: &Self) -> bool {
+        self.len() != other.len() || self.iter().ne(other)
+    }
+}
+
+impl<T: Eq> Eq for GList<T> {}
+
+impl<T: PartialOrd> PartialOrd for GList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for GList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<T: Hash> Hash for GList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for item in self {
+            item.hash(state)
+        }
+    }
+}
+
+impl<T> Index<usize> for GList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("Out of bounds access")
+    }
+}
+
+impl<T> IndexMut<usize> for GList<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("Out of bounds access")
+    }
+}
+
+impl<'a, T> IntoIterator for &'a GList<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.front.map(|node| unsafe {
+                self.len -= 1;
+                self.front = (*node.as_ptr()).back;
+                &(*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.front.map(|node| unsafe {
+                self.len -= 1;
+                self.front = (*node.as_ptr()).back;
+                &mut (*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.back.map(|node| unsafe {
+                self.len -= 1;
+                self.back = (*node.as_ptr()).front;
+                &mut (*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.back.map(|node| unsafe {
+                self.len -= 1;
+                self.back = (*node.as_ptr()).front;
+                &(*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+
+impl<T> IntoIterator for GList<T> {
+    type IntoIter = IntoIter<T>;
+    type Item = T;
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_iter()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.list.len
+    }
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Drain<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Drain<'a, T> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, T, F> Iterator for ExtractIf<'a, T, F>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.next {
+            unsafe {
+                // Step past the node first, it might be about to disappear.
+                // The list is consistent between every step, so if pred
+                // panics we just leave everything else where it is.
+                self.next = (*node.as_ptr()).back;
+                if (self.pred)(&mut (*node.as_ptr()).elem) {
+                    return Some(self.list.unlink_node(node));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.list.len))
+    }
+}
+
+impl<T> FromIterator<T> for GList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+
+impl<T> Clone for NodeHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeHandle<T> {}
+
+impl<T> Debug for NodeHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeHandle")
+            .field("owner", &self.owner)
+            .field("slot", &self.slot)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+// Not derived, since that would want T: Copy too
+impl<'a, T> Clone for Cursor<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Cursor<'a, T> {}
+
+impl<'a, T> Cursor<'a, T> {
+
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = (*cur.as_ptr()).back;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() += 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.front;
+            self.index = Some(0);
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = (*cur.as_ptr()).front;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() -= 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.back;
+            self.index = Some(self.list.len - 1);
+        }
+    }
+
+    // The list is borrowed for 'a, not just for as long as the cursor
+    // so these references can outlive the cursor itself
+    pub fn current(&self) -> Option<&'a T> {
+        unsafe {
+            self.cur.map(|node| &(*node.as_ptr()).elem)
+        }
+    }
+
+    pub fn peek_next(&self) -> Option<&'a T> {
+        unsafe {
+            let next = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).back
+            } else {
+                self.list.front
+            };
+
+            next.map(|node| &(*node.as_ptr()).elem)
+        }
+    }
+
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        unsafe {
+            let prev = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).front
+            } else {
+                self.list.back
+            };
+
+            prev.map(|node| &(*node.as_ptr()).elem)
+        }
+    }
+}
+
+impl<'a, T> CursorMut<'a, T> {
+
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    // Reborrows the list immutably, so you can't mutate while this is alive
+    pub fn as_cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            list: self.list,
+            cur: self.cur,
+            index: self.index,
+        }
+    }
+
+    pub fn move_next(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                // We're on a real element, step to the one behind it
+                self.cur = (*cur.as_ptr()).back;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() += 1;
+                } else {
+                    // Walked off the back and onto the ghost
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            // On the ghost, the next thing is the front of the list
+            self.cur = self.list.front;
+            self.index = Some(0);
+        }
+        // Otherwise we're the ghost of an empty list, nowhere to go
+    }
+
+    pub fn move_prev(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = (*cur.as_ptr()).front;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() -= 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.back;
+            self.index = Some(self.list.len - 1);
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        unsafe {
+            self.cur.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+
+    // Gives up the cursor for a borrow of whatever it was on, so the
+    // reference can outlive the cursor itself
+    pub fn into_current(self) -> Option<&'a mut T> {
+        unsafe {
+            self.cur.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+
+    // A handle to whatever we're on. Asking twice gets the same handle back.
+    pub fn current_handle(&mut self) -> Option<NodeHandle<T>> {
+        let node = self.cur?;
+
+        unsafe { Some(self.list.make_handle(node)) }
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        unsafe {
+            let next = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).back
+            } else {
+                // The ghost's next is the front
+                self.list.front
+            };
+
+            next.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        unsafe {
+            let prev = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).front
+            } else {
+                // The ghost's prev is the back
+                self.list.back
+            };
+
+            prev.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+
+    // On the ghost this is a push_back
+    pub fn insert_before(&mut self, elem: T) {
+        let mut single = GList::new();
+        single.push_back(elem);
+        self.splice_before(single);
+    }
+
+    // On the ghost this is a push_front
+    pub fn insert_after(&mut self, elem: T) {
+        let mut single = GList::new();
+        single.push_back(elem);
+        self.splice_after(single);
+    }
+
+    // Unlinks the current element and moves onto the one after it
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.cur?;
+
+        unsafe {
+            let next = (*node.as_ptr()).back;
+            let elem = self.list.unlink_node(node);
+
+            self.cur = next;
+            if next.is_none() {
+                self.index = None;
+            }
+
+            Some(elem)
+        }
+    }
+
+    // Everything in front of the cursor becomes a new list.
+    // On the ghost we hand back the whole thing.
+    pub fn split_before(&mut self) -> GList<T> {
+        let Some(cur) = self.cur else {
+            return self.list.take_all();
+        };
+
+        unsafe {
+            let index = self.index.unwrap();
+            let Some(prev) = (*cur.as_ptr()).front else {
+                // Already at the front, nothing to split off
+                return GList::new();
+            };
+
+            // Snip!
+            (*cur.as_ptr()).front = None;
+            (*prev.as_ptr()).back = None;
+
+            let output = GList {
+                front: self.list.front,
+                back: Some(prev),
+                len: index,
+                id: 0,
+                handles: Vec::new(),
+                free_handles: Vec::new(),
+                pool: NodePool::new(0),
+                _boo: PhantomData,
+            };
+            self.list.forget_handles_in(output.front);
+
+            self.list.front = Some(cur);
+            self.list.len -= index;
+            self.index = Some(0);
+
+            self.list.after_mutation();
+            output.after_mutation();
+            output
+        }
+    }
+
+    // Everything behind the cursor becomes a new list.
+    // On the ghost we hand back the whole thing.
+    pub fn split_after(&mut self) -> GList<T> {
+        let Some(cur) = self.cur else {
+            return self.list.take_all();
+        };
+
+        unsafe {
+            let index = self.index.unwrap();
+            let Some(next) = (*cur.as_ptr()).back else {
+                // Already at the back, nothing to split off
+                return GList::new();
+            };
+
+            (*cur.as_ptr()).back = None;
+            (*next.as_ptr()).front = None;
+
+            let output = GList {
+                front: Some(next),
+                back: self.list.back,
+                len: self.list.len - index - 1,
+                id: 0,
+                handles: Vec::new(),
+                free_handles: Vec::new(),
+                pool: NodePool::new(0),
+                _boo: PhantomData,
+            };
+            self.list.forget_handles_in(output.front);
+
+            self.list.back = Some(cur);
+            self.list.len = index + 1;
+
+            self.list.after_mutation();
+            output.after_mutation();
+            output
+        }
+    }
+
+    // Links all of input in front of the cursor.
+    // On the ghost it ends up on the back of the list.
+    pub fn splice_before(&mut self, mut input: GList<T>) {
+        if input.is_empty() {
+            return;
+        }
+
+        unsafe {
+            // Steal input's chain, it's left empty so its Drop does nothing
+            let in_front = input.front.take().unwrap();
+            let in_back = input.back.take().unwrap();
+            let in_len = mem::replace(&mut input.len, 0);
+
+            if let Some(cur) = self.cur {
+                if let Some(prev) = (*cur.as_ptr()).front {
+                    (*prev.as_ptr()).back = Some(in_front);
+                    (*in_front.as_ptr()).front = Some(prev);
+                } else {
+                    self.list.front = Some(in_front);
+                }
+                (*cur.as_ptr()).front = Some(in_back);
+                (*in_back.as_ptr()).back = Some(cur);
+
+                // Everything we just added pushed us further from the front
+                *self.index.as_mut().unwrap() += in_len;
+            } else if let Some(back) = self.list.back {
+                (*back.as_ptr()).back = Some(in_front);
+                (*in_front.as_ptr()).front = Some(back);
+                self.list.back = Some(in_back);
+            } else {
+                // We're empty, input's chain just becomes ours
+                self.list.front = Some(in_front);
+                self.list.back = Some(in_back);
+            }
+
+            self.list.len += in_len;
+        }
+
+        self.list.after_mutation();
+    }
+
+    // Links all of input behind the cursor.
+    // On the ghost it ends up on the front of the list.
+    pub fn splice_after(&mut self, mut input: GList<T>) {
+        if input.is_empty() {
+            return;
+        }
+
+        unsafe {
+            let in_front = input.front.take().unwrap();
+            let in_back = input.back.take().unwrap();
+            let in_len = mem::replace(&mut input.len, 0);
+
+            if let Some(cur) = self.cur {
+                if let Some(next) = (*cur.as_ptr()).back {
+                    (*next.as_ptr()).front = Some(in_back);
+                    (*in_back.as_ptr()).back = Some(next);
+                } else {
+                    self.list.back = Some(in_back);
+                }
+                (*cur.as_ptr()).back = Some(in_front);
+                (*in_front.as_ptr()).front = Some(cur);
+            } else if let Some(front) = self.list.front {
+                (*front.as_ptr()).front = Some(in_back);
+                (*in_back.as_ptr()).back = Some(front);
+                self.list.front = Some(in_front);
+            } else {
+                self.list.front = Some(in_front);
+                self.list.back = Some(in_back);
+            }
+
+            self.list.len += in_len;
+        }
+
+        self.list.after_mutation();
+    }
+}
+
+
+// Keeps a half sorted chain in one piece so a panicking comparison
+// can't leave nodes dangling. Every node is always in exactly one of:
+// head..tail (merged so far), the psize nodes starting at p,
+// or the chain starting at q. Drop stitches those back together.
+struct MergeSort<'a, T> {
+    list: &'a mut GList<T>,
+    head: Link<T>,
+    tail: Link<T>,
+    p: Link<T>,
+    psize: usize,
+    q: Link<T>,
+}
+
+impl<'a, T> MergeSort<'a, T> {
+
+    fn new(list: &'a mut GList<T>) -> Self {
+        MergeSort {
+            head: list.front,
+            tail: list.back,
+            list,
+            p: None,
+            psize: 0,
+            q: None,
+        }
+    }
+
+    // One pass over the whole chain, merging each run of pwidth nodes
+    // with the run of qwidth nodes after it. Returns how many merges we did.
+    fn merge_pass<F>(&mut self, pwidth: usize, qwidth: usize, compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        // Last pass's output is this pass's input
+        self.q = self.head.take();
+        self.tail = None;
+        let mut merges = 0;
+
+        unsafe {
+            while self.q.is_some() {
+                merges += 1;
+
+                // p gets the next pwidth nodes, q is whatever's after that
+                self.p = self.q;
+                self.psize = 0;
+                while self.psize < pwidth {
+                    match self.q {
+                        Some(q) => {
+                            self.q = (*q.as_ptr()).back;
+                            self.psize += 1;
+                        }
+                        None => break,
+                    }
+                }
+
+                let mut qsize = qwidth;
+                loop {
+                    let q_ready = qsize > 0 && self.q.is_some();
+                    let take_q = if self.psize == 0 {
+                        if !q_ready {
+                            break;
+                        }
+                        true
+                    } else if !q_ready {
+                        false
+                    } else {
+                        // Only take from q when it's strictly smaller, that's what keeps it stable
+                        let p = &(*self.p.unwrap().as_ptr()).elem;
+                        let q = &(*self.q.unwrap().as_ptr()).elem;
+                        compare(p, q) == Ordering::Greater
+                    };
+
+                    let node = if take_q {
+                        let node = self.q.unwrap();
+                        self.q = (*node.as_ptr()).back;
+                        qsize -= 1;
+                        node
+                    } else {
+                        let node = self.p.unwrap();
+                        self.p = (*node.as_ptr()).back;
+                        self.psize -= 1;
+                        node
+                    };
+                    self.push_merged(node);
+                }
+            }
+
+            if let Some(tail) = self.tail {
+                (*tail.as_ptr()).back = None;
+            }
+        }
+
+        merges
+    }
+
+    // Hangs node off the back of the merged chain
+    unsafe fn push_merged(&mut self, node: NonNull<Node<T>>) {
+        match self.tail {
+            Some(tail) => (*tail.as_ptr()).back = Some(node),
+            None => self.head = Some(node),
+        }
+        (*node.as_ptr()).front = self.tail;
+        self.tail = Some(node);
+    }
+}
 
-        let mut n = GList::new();
-        n.push_front(2);
-        n.push_front(3);
+impl<'a, T> Drop for MergeSort<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            // Normally p and q are both empty by now, but if compare
+            // panicked mid merge we just tack them on unsorted
+            while self.psize > 0 {
+                let node = self.p.unwrap();
+                self.p = (*node.as_ptr()).back;
+                self.psize -= 1;
+                self.push_merged(node);
+            }
+            while let Some(node) = self.q {
+                self.q = (*node.as_ptr()).back;
+                self.push_merged(node);
+            }
+
+            if let Some(tail) = self.tail {
+                (*tail.as_ptr()).back = None;
+            }
+            self.list.front = self.head;
+            self.list.back = self.tail;
+        }
+
+        self.list.after_mutation();
+    }
+}
+
+
+impl<T> NodePool<T> {
+
+    fn new(max_cached: usize) -> Self {
+        NodePool {
+            free: None,
+            cached: 0,
+            max_cached,
+            reused: 0,
+            allocated: 0,
+        }
+    }
+
+    // Frees a node whose elem has already been moved out. Going through
+    // MaybeUninit gets us the same allocation without dropping elem again.
+    unsafe fn release(node: NonNull<Node<T>>) {
+        drop(Box::from_raw(node.as_ptr() as *mut MaybeUninit<Node<T>>));
+    }
+}
+
+
+// Implement Drop
+impl<T> Drop for GList<T> {
+
+    /* In case i fuck this up and im totally wrong 
+    fn drop(&mut self) {
+        // Pop Till We Die
+        while let Some(_) = self.pop_front() {}
+    }
+    */
+
+    fn drop(&mut self) {
+        self.clear();
+        self.shrink_pool();
+    }
+
+}
+
+
+
+
+// The plain deque behaviour every list in this crate has to match.
+// Stamps out a module of tests against whatever list type it's handed,
+// so the other flavours can prove they act just like GList.
+#[cfg(test)]
+macro_rules! deque_tests {
+    ($list:ident) => {
+        mod deque {
+
+            use super::$list;
+
+            fn generate_test() -> $list<i32> {
+                list_from(&[0, 1, 2, 3, 4, 5, 6])
+            }
+
+            fn list_from<T: Clone>(v: &[T]) -> $list<T> {
+                v.iter().map(|x| (*x).clone()).collect()
+            }
+
+            #[test]
+            fn test_basic_front() {
+                let mut list = $list::new();
+
+                // Try to break an empty list
+                assert_eq!(list.len(), 0);
+                assert_eq!(list.pop_front(), None);
+                assert_eq!(list.len(), 0);
+
+                // Try to break a one item list
+                list.push_front(10);
+                assert_eq!(list.len(), 1);
+                assert_eq!(list.pop_front(), Some(10));
+                assert_eq!(list.len(), 0);
+                assert_eq!(list.pop_front(), None);
+                assert_eq!(list.len(), 0);
+
+                // Mess around
+                list.push_front(10);
+                assert_eq!(list.len(), 1);
+                list.push_front(20);
+                assert_eq!(list.len(), 2);
+                list.push_front(30);
+                assert_eq!(list.len(), 3);
+                assert_eq!(list.pop_front(), Some(30));
+                assert_eq!(list.len(), 2);
+                list.push_front(40);
+                assert_eq!(list.len(), 3);
+                assert_eq!(list.pop_front(), Some(40));
+                assert_eq!(list.len(), 2);
+                assert_eq!(list.pop_front(), Some(20));
+                assert_eq!(list.len(), 1);
+                assert_eq!(list.pop_front(), Some(10));
+                assert_eq!(list.len(), 0);
+                assert_eq!(list.pop_front(), None);
+                assert_eq!(list.len(), 0);
+                assert_eq!(list.pop_front(), None);
+                assert_eq!(list.len(), 0);
+
+            }
+
+
+            #[test]
+            fn test_basic() {
+                let mut m = $list::new();
+
+                assert_eq!(m.pop_front(), None);
+                assert_eq!(m.pop_back(), None);
+                assert_eq!(m.pop_front(), None);
+                m.push_front(1);
+                assert_eq!(m.pop_front(), Some(1));
+                m.push_back(2);
+                m.push_back(3);
+                assert_eq!(m.len(), 2);
+                assert_eq!(m.pop_front(), Some(2));
+                assert_eq!(m.pop_front(), Some(3));
+                assert_eq!(m.len(), 0);
+                assert_eq!(m.pop_front(), None);
+                m.push_back(1);
+                m.push_back(3);
+                m.push_back(5);
+                m.push_back(7);
+                assert_eq!(m.pop_front(), Some(1));
+
+                let mut n = $list::new();
+                n.push_front(2);
+                n.push_front(3);
+                {
+                    assert_eq!(n.front().unwrap(), &3);
+                    let x = n.front_mut().unwrap();
+                    assert_eq!(*x, 3);
+                    *x = 0;
+                }
+                {
+                    assert_eq!(n.back().unwrap(), &2);
+                    let y = n.back_mut().unwrap();
+                    assert_eq!(*y, 2);
+                    *y = 1;
+                }
+                assert_eq!(n.pop_front(), Some(0));
+                assert_eq!(n.pop_front(), Some(1));
+            }
+
+
+
+            #[test]
+            fn test_iterator() {
+
+                let m = generate_test();
+                for (i, elt) in m.iter().enumerate() {
+                    assert_eq!(i as i32, *elt);
+                }
+                let mut n = $list::new();
+                assert_eq!(n.iter().next(), None);
+                n.push_front(4);
+                let mut it = n.iter();
+                assert_eq!(it.size_hint(), (1, Some(1)));
+                assert_eq!(it.next().unwrap(), &4);
+                assert_eq!(it.size_hint(), (0, Some(0)));
+                assert_eq!(it.next(), None);
+            }
+
+            #[test]
+            fn test_iterator_double_end() {
+                let mut n = $list::new();
+                assert_eq!(n.iter().next(), None);
+                n.push_front(4);
+                n.push_front(5);
+                n.push_front(6);
+                let mut it = n.iter();
+                assert_eq!(it.size_hint(), (3, Some(3)));
+                assert_eq!(it.next().unwrap(), &6);
+                assert_eq!(it.size_hint(), (2, Some(2)));
+                assert_eq!(it.next_back().unwrap(), &4);
+                assert_eq!(it.size_hint(), (1, Some(1)));
+                assert_eq!(it.next_back().unwrap(), &5);
+                assert_eq!(it.next_back(), None);
+                assert_eq!(it.next(), None);
+            }
+
+            #[test]
+            fn test_rev_iter() {
+                let m = generate_test();
+                for (i, elt) in m.iter().rev().enumerate() {
+                    assert_eq!(6 - i as i32, *elt);
+                }
+
+                let mut n = $list::new();
+                assert_eq!(n.iter().rev().next(), None);
+                n.push_front(4);
+                let mut it = n.iter().rev();
+                assert_eq!(it.size_hint(), (1, Some(1)));
+                assert_eq!(it.next().unwrap(), &4);
+                assert_eq!(it.size_hint(), (0, Some(0)));
+                assert_eq!(it.next(), None);
+            }
+
+
+            #[test]
+            fn text_mut_iter() {
+                let mut m = generate_test();
+                let mut len = m.len();
+                for (i, elt) in m.iter_mut().enumerate() {
+                    assert_eq!(i as i32, *elt);
+                    len -= 1;
+                }
+
+                assert_eq!(len, 0);
+                let mut n = $list::new();
+                assert!(n.iter_mut().next().is_none());
+                n.push_front(4);
+                n.push_back(5);
+                let mut it = n.iter_mut();
+                assert_eq!(it.size_hint(), (2, Some(2)));
+                assert!(it.next().is_some());
+                assert!(it.next().is_some());
+                assert_eq!(it.size_hint(), (0, Some(0)));
+                assert!(it.next().is_none());
+            }
+
+            #[test]
+            fn text_iterator_mut_double_end() {
+                let mut n = $list::new();
+                assert!(n.iter_mut().next_back().is_none());
+                n.push_front(4);
+                n.push_front(5);
+                n.push_front(6);
+                let mut it = n.iter_mut();
+                assert_eq!(it.size_hint(), (3, Some(3)));
+                assert_eq!(*it.next().unwrap(), 6);
+                assert_eq!(it.size_hint(), (2, Some(2)));
+                assert_eq!(*it.next_back().unwrap(), 4);
+                assert_eq!(it.size_hint(), (1, Some(1)));
+                assert_eq!(*it.next_back().unwrap(), 5);
+                assert!(it.next_back().is_none());
+                assert!(it.next().is_none());
+            }
+
+
+            #[test]
+            fn test_eq() {
+                let mut n: $list<u8> = list_from(&[]);
+                let mut m = list_from(&[]);
+                assert!(n == m);
+                n.push_front(1);
+                assert!(n != m);
+                m.push_back(1);
+                assert!(n == m);
+
+                let n = list_from(&[2, 3, 4]);
+                let m = list_from(&[1, 2, 3]);
+                assert!(n != m);
+            }
+
+            #[test]
+            fn test_ord() {
+                let n = list_from(&[]);
+                let m = list_from(&[1, 2, 3]);
+                assert!(n < m);
+                assert!(m > n);
+                assert!(n <= n);
+                assert!(n >= n);
+            }
+
+            // NaN is the whole point here, so the negated comparisons stay
+            #[test]
+            #[allow(clippy::neg_cmp_op_on_partial_ord)]
+            fn test_ord_nan() {
+                let nan = 0.0f64 / 0.0;
+                let n = list_from(&[nan]);
+                let m = list_from(&[nan]);
+                assert!(!(n < m));
+                assert!(!(n > m));
+                assert!(!(n <= m));
+                assert!(!(n >= m));
+
+                let n = list_from(&[nan]);
+                let one = list_from(&[1.0f64]);
+                assert!(!(n < one));
+                assert!(!(n > one));
+                assert!(!(n <= one));
+                assert!(!(n >= one));
+
+                let u = list_from(&[1.0f64, 2.0, nan]);
+                let v = list_from(&[1.0f64, 2.0, 3.0]);
+                assert!(!(u < v));
+                assert!(!(u > v));
+                assert!(!(u <= v));
+                assert!(!(u >= v));
+
+                let s = list_from(&[1.0f64, 2.0, 4.0, 2.0]);
+                let t = list_from(&[1.0f64, 2.0, 3.0, 2.0]);
+                assert!(!(s < t));
+                assert!(s > one);
+                assert!(!(s <= one));
+                assert!(s >= one);
+            }
+
+            #[test]
+            fn test_debug() {
+                let list: $list<i32> = (0..10).collect();
+                assert_eq!(format!("{:?}", list), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]");
+
+                let list: $list<&str> = vec!["just", "one", "test", "more"]
+                    .iter().copied()
+                    .collect();
+                assert_eq!(format!("{:?}", list), r#"["just", "one", "test", "more"]"#);
+            }
+
+            #[test]
+            fn test_hashmap() {
+                // Check that HashMap works with this as a key
+
+                let list1: $list<i32> = (0..10).collect();
+                let list2: $list<i32> = (1..11).collect();
+                let mut map = std::collections::HashMap::new();
+
+                assert_eq!(map.insert(list1.clone(), "list1"), None);
+                assert_eq!(map.insert(list2.clone(), "list2"), None);
+
+                assert_eq!(map.len(), 2);
+
+                assert_eq!(map.get(&list1), Some(&"list1"));
+                assert_eq!(map.get(&list2), Some(&"list2"));
+
+                assert_eq!(map.remove(&list1), Some("list1"));
+                assert_eq!(map.remove(&list2), Some("list2"));
+
+                assert!(map.is_empty());
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use deque_tests;
+
+
+#[cfg(test)]
+mod test {
+
+    use super::GList;
+    use std::fmt::Debug;
+
+
+
+    fn list_from<T: Clone>(v: &[T]) -> GList<T> {
+        v.iter().map(|x| (*x).clone()).collect()
+    }
+
+    // Walks the list from both ends so broken front/back links show up
+    fn assert_both_ways<T: PartialEq + Debug>(list: &GList<T>, expected: &[T]) {
+        assert_eq!(list.len(), expected.len());
+        assert!(list.iter().eq(expected.iter()));
+        assert!(list.iter().rev().eq(expected.iter().rev()));
+    }
+
+
+    deque_tests!(GList);
+
+
+    #[test]
+    fn test_cursor_move_peek() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3, 4, 5, 6]);
+        let mut cursor = m.cursor_mut();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 1));
+        assert_eq!(cursor.peek_prev(), Some(&mut 6));
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.peek_next(), Some(&mut 2));
+        assert_eq!(cursor.peek_prev(), None);
+        assert_eq!(cursor.index(), Some(0));
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 6));
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.index(), Some(5));
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(0));
+
+        cursor.move_next();
+        *cursor.current().unwrap() = 20;
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(m.iter().nth(1), Some(&20));
+
+        let mut m: GList<u32> = list_from(&[1, 2, 3, 4, 5, 6]);
+        let mut cursor = m.cursor_back_mut();
+        assert_eq!(cursor.current(), Some(&mut 6));
+        assert_eq!(cursor.index(), Some(5));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 5));
+        assert_eq!(cursor.index(), Some(4));
+
+        let mut empty: GList<u32> = GList::new();
+        let mut cursor = empty.cursor_front_mut();
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), None);
+    }
+
+    #[test]
+    fn test_cursor_insert_remove() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3]);
+        let mut cursor = m.cursor_mut();
+
+        // On the ghost, inserts go on the ends
+        cursor.insert_after(0);
+        cursor.insert_before(4);
+        assert_eq!(cursor.index(), None);
+        assert_both_ways(&m, &[0, 1, 2, 3, 4]);
+
+        let mut cursor = m.cursor_front_mut();
+        cursor.move_next();
+        cursor.insert_before(10);
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 1));
+        cursor.insert_after(11);
+        assert_eq!(cursor.peek_next(), Some(&mut 11));
+        assert_both_ways(&m, &[0, 10, 1, 11, 2, 3, 4]);
+
+        let mut cursor = m.cursor_front_mut();
+        assert_eq!(cursor.remove_current(), Some(0));
+        assert_eq!(cursor.current(), Some(&mut 10));
+        assert_eq!(cursor.index(), Some(0));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(11));
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(cursor.index(), Some(2));
+        assert_both_ways(&m, &[10, 1, 2, 3, 4]);
+
+        let mut cursor = m.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(4));
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.remove_current(), None);
+        assert_both_ways(&m, &[10, 1, 2, 3]);
+
+        let mut cursor = m.cursor_front_mut();
+        while cursor.remove_current().is_some() {}
+        assert_both_ways(&m, &[]);
+
+        m.cursor_mut().insert_after(7);
+        assert_both_ways(&m, &[7]);
+    }
+
+    #[test]
+    fn test_cursor_split() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3, 4, 5, 6]);
+        let mut cursor = m.cursor_front_mut();
+        cursor.move_next();
+        cursor.move_next();
+
+        let front = cursor.split_before();
+        assert_eq!(cursor.index(), Some(0));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        let back = cursor.split_after();
+        assert_eq!(cursor.index(), Some(0));
+        assert_both_ways(&front, &[1, 2]);
+        assert_both_ways(&back, &[4, 5, 6]);
+        assert_both_ways(&m, &[3]);
+
+        // Splitting off nothing from either end
+        let mut cursor = m.cursor_front_mut();
+        assert!(cursor.split_before().is_empty());
+        assert!(cursor.split_after().is_empty());
+        assert_both_ways(&m, &[3]);
+
+        // The ghost gives up the whole list
+        let mut m: GList<u32> = list_from(&[1, 2, 3]);
+        let all = m.cursor_mut().split_after();
+        assert_both_ways(&all, &[1, 2, 3]);
+        assert_both_ways(&m, &[]);
+    }
+
+    #[test]
+    fn test_cursor_splice() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3]);
+        let mut cursor = m.cursor_front_mut();
+        cursor.move_next();
+
+        cursor.splice_before(list_from(&[7, 8]));
+        assert_eq!(cursor.index(), Some(3));
+        assert_eq!(cursor.current(), Some(&mut 2));
+        cursor.splice_after(list_from(&[9, 10]));
+        assert_eq!(cursor.peek_next(), Some(&mut 9));
+        cursor.splice_after(GList::new());
+        cursor.splice_before(GList::new());
+        assert_eq!(cursor.index(), Some(3));
+        assert_both_ways(&m, &[1, 7, 8, 2, 9, 10, 3]);
+
+        // Splicing on the ends, and through the ghost
+        let mut cursor = m.cursor_back_mut();
+        cursor.splice_after(list_from(&[11]));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        cursor.splice_after(list_from(&[0]));
+        cursor.splice_before(list_from(&[12]));
+        assert_both_ways(&m, &[0, 1, 7, 8, 2, 9, 10, 3, 11, 12]);
+
+        let mut empty: GList<u32> = GList::new();
+        empty.cursor_mut().splice_before(list_from(&[1, 2]));
+        assert_both_ways(&empty, &[1, 2]);
+    }
+
+
+    #[test]
+    fn test_cursor_read_only() {
+        let m: GList<u32> = list_from(&[1, 2, 3, 4]);
+        let mut cursor = m.cursor();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&1));
+        assert_eq!(cursor.peek_prev(), Some(&4));
+
+        cursor.move_next();
+        let saved = cursor;
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(saved.current(), Some(&1));
+        assert_eq!(saved.index(), Some(0));
+        assert_eq!(cursor.current(), Some(&3));
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.peek_prev(), Some(&2));
+        assert_eq!(cursor.peek_next(), Some(&4));
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&4));
+        assert_eq!(cursor.index(), Some(3));
+
+        // References come from the list, not the cursor
+        let elem = {
+            let c = m.cursor_back();
+            c.current()
+        };
+        assert_eq!(elem, Some(&4));
+
+        // Two-pointer walk from both ends
+        let mut lo = m.cursor_front();
+        let mut hi = m.cursor_back();
+        let mut pairs = Vec::new();
+        while lo.index() < hi.index() {
+            pairs.push((*lo.current().unwrap(), *hi.current().unwrap()));
+            lo.move_next();
+            hi.move_prev();
+        }
+        assert_eq!(pairs, vec![(1, 4), (2, 3)]);
+    }
+
+    #[test]
+    fn test_cursor_as_cursor() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3]);
+        let mut cursor = m.cursor_mut();
+        cursor.move_next();
+        cursor.move_next();
         {
-            assert_eq!(n.front().unwrap(), &3);
-            let x = n.front_mut().unwrap();
-            assert_eq!(*x, 3);
-            *x = 0;
+            let mut peek = cursor.as_cursor();
+            assert_eq!(peek.current(), Some(&2));
+            assert_eq!(peek.index(), Some(1));
+            peek.move_next();
+            assert_eq!(peek.current(), Some(&3));
+        }
+        // Moving the copy doesn't move the original
+        assert_eq!(cursor.current(), Some(&mut 2));
+        cursor.remove_current();
+        assert_eq!(cursor.as_cursor().current(), Some(&3));
+        assert_eq!(cursor.as_cursor().index(), Some(1));
+    }
+
+
+    #[test]
+    fn test_append() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3]);
+        let mut n: GList<u32> = list_from(&[4, 5]);
+        m.append(&mut n);
+        assert_both_ways(&m, &[1, 2, 3, 4, 5]);
+        assert_both_ways(&n, &[]);
+
+        // Appending nothing, and appending onto nothing
+        m.append(&mut n);
+        assert_both_ways(&m, &[1, 2, 3, 4, 5]);
+        n.append(&mut m);
+        assert_both_ways(&n, &[1, 2, 3, 4, 5]);
+        assert_both_ways(&m, &[]);
+
+        // The emptied list is still perfectly usable
+        m.push_back(9);
+        m.push_front(8);
+        assert_both_ways(&m, &[8, 9]);
+
+        let mut front: GList<u32> = list_from(&[6, 7]);
+        m.prepend_list(&mut front);
+        assert_both_ways(&m, &[6, 7, 8, 9]);
+        assert!(front.is_empty());
+        front.prepend_list(&mut m);
+        assert_both_ways(&front, &[6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_split_off() {
+        // Each split point from both halves of the list
+        for at in 0..=6 {
+            let mut m: GList<u32> = list_from(&[0, 1, 2, 3, 4, 5]);
+            let tail = m.split_off(at);
+            let expected: Vec<u32> = (0..6).collect();
+            assert_both_ways(&m, &expected[..at]);
+            assert_both_ways(&tail, &expected[at..]);
         }
+
+        let mut empty: GList<u32> = GList::new();
+        assert!(empty.split_off(0).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_off_out_of_bounds() {
+        let mut m: GList<u32> = list_from(&[1, 2, 3]);
+        m.split_off(4);
+    }
+
+
+    #[test]
+    fn test_retain() {
+        let mut m: GList<u32> = (0..10).collect();
+        m.retain(|x| x % 3 != 0);
+        assert_both_ways(&m, &[1, 2, 4, 5, 7, 8]);
+
+        m.retain_mut(|x| {
+            *x *= 10;
+            *x > 40
+        });
+        assert_both_ways(&m, &[50, 70, 80]);
+
+        m.retain(|_| false);
+        assert_both_ways(&m, &[]);
+        m.retain(|_| true);
+        assert_both_ways(&m, &[]);
+    }
+
+    #[test]
+    fn test_retain_keeps_nodes() {
+        // Survivors should be the same allocations they were before
+        let mut m: GList<u32> = (0..6).collect();
+        let before: Vec<*const u32> = m.iter().map(|x| x as *const u32).collect();
+        m.retain(|x| x % 2 == 0);
+        let after: Vec<*const u32> = m.iter().map(|x| x as *const u32).collect();
+        assert_eq!(after, vec![before[0], before[2], before[4]]);
+    }
+
+    #[test]
+    fn test_extract_if() {
+        let mut m: GList<u32> = (0..10).collect();
+        let evens: Vec<u32> = m.extract_if(|x| *x % 2 == 0).collect();
+        assert_eq!(evens, vec![0, 2, 4, 6, 8]);
+        assert_both_ways(&m, &[1, 3, 5, 7, 9]);
+
+        // Stopping early leaves the rest alone
         {
-            assert_eq!(n.back().unwrap(), &2);
-            let y = n.back_mut().unwrap();
-            assert_eq!(*y, 2);
-            *y = 1;
+            let mut it = m.extract_if(|x| *x > 2);
+            assert_eq!(it.next(), Some(3));
         }
-        assert_eq!(n.pop_front(), Some(0));
-        assert_eq!(n.pop_front(), Some(1));
+        assert_both_ways(&m, &[1, 5, 7, 9]);
+
+        // Predicate gets to mutate the ones it keeps
+        let taken: Vec<u32> = m.extract_if(|x| {
+            *x += 1;
+            *x == 10
+        }).collect();
+        assert_eq!(taken, vec![10]);
+        assert_both_ways(&m, &[2, 6, 8]);
+    }
+
+    #[test]
+    fn test_extract_if_panic() {
+        let mut m: GList<u32> = (0..8).collect();
+        let mut seen = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            m.retain(|x| {
+                seen += 1;
+                if *x == 5 {
+                    panic!("boom");
+                }
+                x % 2 == 0
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(seen, 6);
+        // Everything before the panic got filtered, everything after is untouched
+        assert_both_ways(&m, &[0, 2, 4, 5, 6, 7]);
     }
 
 
+    #[test]
+    fn test_drain() {
+        let mut m: GList<u32> = (0..10).collect();
+        let drained: Vec<u32> = m.drain(2..5).collect();
+        assert_eq!(drained, vec![2, 3, 4]);
+        assert_both_ways(&m, &[0, 1, 5, 6, 7, 8, 9]);
+
+        let drained: Vec<u32> = m.drain(..=1).rev().collect();
+        assert_eq!(drained, vec![1, 0]);
+        assert_both_ways(&m, &[5, 6, 7, 8, 9]);
+
+        let drained: Vec<u32> = m.drain(3..).collect();
+        assert_eq!(drained, vec![8, 9]);
+        assert_both_ways(&m, &[5, 6, 7]);
+
+        assert_eq!(m.drain(1..1).count(), 0);
+        assert_both_ways(&m, &[5, 6, 7]);
+
+        let drained: Vec<u32> = m.drain(..).collect();
+        assert_eq!(drained, vec![5, 6, 7]);
+        assert_both_ways(&m, &[]);
+        m.push_back(1);
+        assert_both_ways(&m, &[1]);
+    }
 
     #[test]
-    fn test_iterator() {
+    fn test_drain_double_ended() {
+        let mut m: GList<u32> = (0..8).collect();
+        let mut d = m.drain(1..7);
+        assert_eq!(d.len(), 6);
+        assert_eq!(d.next(), Some(1));
+        assert_eq!(d.next_back(), Some(6));
+        assert_eq!(d.size_hint(), (4, Some(4)));
+        assert_eq!(d.next_back(), Some(5));
+        assert_eq!(d.len(), 3);
+    }
 
-        let m = generate_test();
-        for (i, elt) in m.iter().enumerate() {
-            assert_eq!(i as i32, *elt);
+    #[test]
+    fn test_drain_dropped_early() {
+        let mut m: GList<u32> = (0..8).collect();
+        {
+            let mut d = m.drain(2..6);
+            assert_eq!(d.next(), Some(2));
         }
-        let mut n = GList::new();
-        assert_eq!(n.iter().next(), None);
-        n.push_front(4);
-        let mut it = n.iter();
-        assert_eq!(it.size_hint(), (1, Some(1)));
-        assert_eq!(it.next().unwrap(), &4);
-        assert_eq!(it.size_hint(), (0, Some(0)));
-        assert_eq!(it.next(), None);
+        // The rest of the range is gone, and the tail is back where it was
+        assert_both_ways(&m, &[0, 1, 6, 7]);
+
+        // Never touching the Drain at all still removes the range
+        m.drain(1..3);
+        assert_both_ways(&m, &[0, 7]);
     }
 
     #[test]
-    fn test_iterator_double_end() {
-        let mut n = GList::new();
-        assert_eq!(n.iter().next(), None);
-        n.push_front(4);
-        n.push_front(5);
-        n.push_front(6);
-        let mut it = n.iter();
-        assert_eq!(it.size_hint(), (3, Some(3)));
-        assert_eq!(it.next().unwrap(), &6);
-        assert_eq!(it.size_hint(), (2, Some(2)));
-        assert_eq!(it.next_back().unwrap(), &4);
-        assert_eq!(it.size_hint(), (1, Some(1)));
-        assert_eq!(it.next_back().unwrap(), &5);
-        assert_eq!(it.next_back(), None);
-        assert_eq!(it.next(), None);
+    #[should_panic]
+    fn test_drain_out_of_bounds() {
+        let mut m: GList<u32> = (0..3).collect();
+        m.drain(1..4);
+    }
+
+
+    // Tiny LCG so the sort tests don't need a rand dependency
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 33) as u32 % 100
+            })
+            .collect()
     }
 
     #[test]
-    fn test_rev_iter() {
-        let m = generate_test();
-        for (i, elt) in m.iter().rev().enumerate() {
-            assert_eq!(6 - i as i32, *elt);
+    fn test_sort() {
+        for len in [0, 1, 2, 3, 7, 8, 9, 64, 100, 257] {
+            let v = pseudo_random(len, len as u64);
+            let mut m: GList<u32> = list_from(&v);
+            m.sort();
+
+            let mut expected = v.clone();
+            expected.sort();
+            assert_both_ways(&m, &expected);
+            assert!(m.is_sorted());
         }
 
-        let mut n = GList::new();
-        assert_eq!(n.iter().rev().next(), None);
-        n.push_front(4);
-        let mut it = n.iter().rev();
-        assert_eq!(it.size_hint(), (1, Some(1)));
-        assert_eq!(it.next().unwrap(), &4);
-        assert_eq!(it.size_hint(), (0, Some(0)));
-        assert_eq!(it.next(), None);
+        let mut m: GList<u32> = (0..50).rev().collect();
+        assert!(!m.is_sorted());
+        m.sort_by(|a, b| b.cmp(a));
+        assert_both_ways(&m, &(0..50).rev().collect::<Vec<u32>>());
+        m.sort();
+        assert_both_ways(&m, &(0..50).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_sort_stable() {
+        // Pair each key with its original position, then sort on the key alone
+        let keys = pseudo_random(200, 42);
+        let v: Vec<(u32, usize)> = keys.iter().map(|k| k % 10).zip(0..).collect();
+        let mut m: GList<(u32, usize)> = list_from(&v);
+        m.sort_by_key(|&(k, _)| k);
+
+        let mut expected = v.clone();
+        expected.sort_by_key(|&(k, _)| k);
+        assert_both_ways(&m, &expected);
+    }
+
+    #[test]
+    fn test_sort_keeps_nodes() {
+        let mut m: GList<u32> = list_from(&[3, 1, 2]);
+        let before: Vec<*const u32> = m.iter().map(|x| x as *const u32).collect();
+        m.sort();
+        let after: Vec<*const u32> = m.iter().map(|x| x as *const u32).collect();
+        assert_eq!(after, vec![before[1], before[2], before[0]]);
+    }
+
+    #[test]
+    fn test_sort_panic() {
+        let v = pseudo_random(100, 7);
+        let mut m: GList<u32> = list_from(&v);
+        let mut calls = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            m.sort_by(|a, b| {
+                calls += 1;
+                if calls == 150 {
+                    panic!("boom");
+                }
+                a.cmp(b)
+            });
+        }));
+        assert!(result.is_err());
+
+        // Not sorted, but every element is still there and the links are sane
+        let mut got: Vec<u32> = m.iter().copied().collect();
+        got.sort();
+        let mut expected = v.clone();
+        expected.sort();
+        assert_eq!(got, expected);
+        let forward: Vec<u32> = m.iter().copied().collect();
+        assert_both_ways(&m, &forward);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut m: GList<u32> = list_from(&[1, 3, 5, 7, 9]);
+        let mut n: GList<u32> = list_from(&[2, 3, 4, 10, 11, 12]);
+        m.merge(&mut n);
+        assert_both_ways(&m, &[1, 2, 3, 3, 4, 5, 7, 9, 10, 11, 12]);
+        assert_both_ways(&n, &[]);
+
+        let mut empty: GList<u32> = GList::new();
+        m.merge(&mut empty);
+        assert_eq!(m.len(), 11);
+        empty.merge(&mut m);
+        assert_eq!(empty.len(), 11);
+        assert!(empty.is_sorted());
+
+        // Ours win ties
+        let mut a: GList<(u32, char)> = list_from(&[(1, 'a'), (2, 'a')]);
+        let mut b: GList<(u32, char)> = list_from(&[(1, 'b'), (2, 'b')]);
+        a.merge(&mut b);
+        assert_both_ways(&a, &[(1, 'a'), (1, 'b'), (2, 'a'), (2, 'b')]);
     }
 
 
     #[test]
-    fn text_mut_iter() {
-        let mut m = generate_test();
-        let mut len = m.len();
-        for (i, elt) in m.iter_mut().enumerate() {
-            assert_eq!(i as i32, *elt);
-            len -= 1;
+    fn test_get() {
+        let mut m: GList<u32> = (0..9).collect();
+        for i in 0..9 {
+            assert_eq!(m.get(i), Some(&(i as u32)));
+            assert_eq!(m[i], i as u32);
         }
+        assert_eq!(m.get(9), None);
 
-        assert_eq!(len, 0);
-        let mut n = GList::new();
-        assert!(n.iter_mut().next().is_none());
-        n.push_front(4);
-        n.push_back(5);
-        let mut it = n.iter_mut();
-        assert_eq!(it.size_hint(), (2, Some(2)));
-        assert!(it.next().is_some());
-        assert!(it.next().is_some());
-        assert_eq!(it.size_hint(), (0, Some(0)));
-        assert!(it.next().is_none());
+        *m.get_mut(7).unwrap() = 70;
+        m[1] = 10;
+        assert_both_ways(&m, &[0, 10, 2, 3, 4, 5, 6, 70, 8]);
+
+        let empty: GList<u32> = GList::new();
+        assert_eq!(empty.get(0), None);
     }
 
     #[test]
-    fn text_iterator_mut_double_end() {
-        let mut n = GList::new();
-        assert!(n.iter_mut().next_back().is_none());
-        n.push_front(4);
-        n.push_front(5);
-        n.push_front(6);
-        let mut it = n.iter_mut();
-        assert_eq!(it.size_hint(), (3, Some(3)));
-        assert_eq!(*it.next().unwrap(), 6);
-        assert_eq!(it.size_hint(), (2, Some(2)));
-        assert_eq!(*it.next_back().unwrap(), 4);
-        assert_eq!(it.size_hint(), (1, Some(1)));
-        assert_eq!(*it.next_back().unwrap(), 5);
-        assert!(it.next_back().is_none());
-        assert!(it.next().is_none());
-    }
-
-
-    #[test]
-    fn test_eq() {
-        let mut n: GList<u8> = list_from(&[]);
-        let mut m = list_from(&[]);
-        assert!(n == m);
-        n.push_front(1);
-        assert!(n != m);
+    #[should_panic]
+    fn test_index_out_of_bounds() {
+        let m: GList<u32> = (0..3).collect();
+        let _ = m[3];
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut m: GList<u32> = GList::new();
+        m.insert(0, 2);
+        m.insert(0, 0);
+        m.insert(2, 4);
+        m.insert(1, 1);
+        m.insert(3, 3);
+        assert_both_ways(&m, &[0, 1, 2, 3, 4]);
+
+        assert_eq!(m.remove(5), None);
+        assert_eq!(m.remove(3), Some(3));
+        assert_eq!(m.remove(0), Some(0));
+        assert_both_ways(&m, &[1, 2, 4]);
+        assert_eq!(m.remove(2), Some(4));
+        assert_eq!(m.remove(1), Some(2));
+        assert_eq!(m.remove(0), Some(1));
+        assert_both_ways(&m, &[]);
+        assert_eq!(m.remove(0), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_out_of_bounds() {
+        let mut m: GList<u32> = (0..3).collect();
+        m.insert(4, 0);
+    }
+
+
+    #[test]
+    fn test_handles() {
+        let mut m: GList<u32> = GList::new();
+        let two = m.push_back_handle(2);
+        let one = m.push_front_handle(1);
+        let three = m.push_back_handle(3);
+        m.push_back(4);
+        assert_eq!(m.get_by_handle(&one), Some(&1));
+        assert_eq!(m.get_by_handle(&three), Some(&3));
+
+        *m.get_by_handle_mut(&two).unwrap() = 20;
+        assert_both_ways(&m, &[1, 20, 3, 4]);
+
+        assert!(m.move_to_front(&three));
+        assert_both_ways(&m, &[3, 1, 20, 4]);
+        assert!(m.move_to_back(&one));
+        assert_both_ways(&m, &[3, 20, 4, 1]);
+        assert!(m.move_to_back(&one));
+        assert!(m.move_to_front(&three));
+        assert_both_ways(&m, &[3, 20, 4, 1]);
+
+        assert_eq!(m.remove_handle(&two), Some(20));
+        assert_both_ways(&m, &[3, 4, 1]);
+        assert_eq!(m.get_by_handle(&one), Some(&1));
+    }
+
+    #[test]
+    fn test_handles_stale() {
+        let mut m: GList<u32> = GList::new();
+        let one = m.push_back_handle(1);
+        let two = m.push_back_handle(2);
+        let three = m.push_back_handle(3);
+
+        // Removed every which way, the handle goes stale
+        assert_eq!(m.remove_handle(&one), Some(1));
+        assert_eq!(m.remove_handle(&one), None);
+        assert_eq!(m.pop_back(), Some(3));
+        assert_eq!(m.get_by_handle(&three), None);
+        assert!(!m.move_to_front(&three));
+        m.retain(|_| false);
+        assert_eq!(m.get_by_handle(&two), None);
+
+        // Even if a new node happens to land at the same address
+        m.push_back(7);
+        let eight = m.push_back_handle(8);
+        assert_eq!(m.get_by_handle(&two), None);
+        assert_eq!(m.remove_handle(&three), None);
+        assert_both_ways(&m, &[7, 8]);
+        assert_eq!(m.get_by_handle(&eight), Some(&8));
+
+        m.clear();
+        assert_eq!(m.get_by_handle(&eight), None);
+    }
+
+    #[test]
+    fn test_handles_foreign() {
+        let mut m: GList<u32> = GList::new();
+        let mut n: GList<u32> = GList::new();
+        let mine = m.push_back_handle(1);
+        let theirs = n.push_back_handle(2);
+
+        assert_eq!(m.get_by_handle(&theirs), None);
+        assert_eq!(m.remove_handle(&theirs), None);
+        assert!(!n.move_to_back(&mine));
+        assert_both_ways(&m, &[1]);
+        assert_both_ways(&n, &[2]);
+
+        // Clones get fresh nodes, so the handle doesn't work on them
+        let cloned = m.clone();
+        assert_eq!(cloned.get_by_handle(&mine), None);
+    }
+
+    #[test]
+    fn test_handles_follow_nodes() {
+        // Handles to nodes that stay put survive everything, handles to
+        // nodes that move to another list go stale
+        let mut m: GList<u32> = GList::new();
+        let mut n: GList<u32> = GList::new();
+        let one = m.push_back_handle(1);
+        let two = n.push_back_handle(2);
+        let three = n.push_back_handle(3);
+
+        m.append(&mut n);
+        assert_eq!(m.get_by_handle(&one), Some(&1));
+        assert_eq!(m.get_by_handle(&two), None);
+        assert_eq!(n.get_by_handle(&two), None);
+        let mut cursor = m.cursor_front_mut();
+        cursor.move_next();
+        let two = cursor.current_handle().unwrap();
+        assert_eq!(m.get_by_handle(&two), Some(&2));
+
+        let mut tail = m.split_off(2);
+        assert_eq!(m.get_by_handle(&three), None);
+        assert_eq!(tail.get_by_handle(&three), None);
+        assert_eq!(m.get_by_handle(&one), Some(&1));
+        assert_eq!(m.get_by_handle(&two), Some(&2));
+        assert_both_ways(&tail, &[3]);
+
+        let zero = tail.push_front_handle(0);
+        m.prepend_list(&mut tail);
+        assert_eq!(m.get_by_handle(&zero), None);
+        assert_eq!(m.remove_handle(&two), Some(2));
+        let two = m.push_back_handle(2);
+        let four = m.push_back_handle(4);
+        assert_both_ways(&m, &[0, 3, 1, 2, 4]);
+
+        let drained: Vec<u32> = m.drain(2..3).collect();
+        assert_eq!(drained, vec![1]);
+        assert_eq!(m.get_by_handle(&one), None);
+        assert_eq!(m.get_by_handle(&four), Some(&4));
+        assert_eq!(m.remove_handle(&two), Some(2));
+
+        let mut cursor = m.cursor_back_mut();
+        let front = cursor.split_before();
+        assert_both_ways(&front, &[0, 3]);
+        assert_eq!(m.get_by_handle(&four), Some(&4));
+        let mut cursor = m.cursor_front_mut();
+        cursor.splice_before(front);
+        assert_eq!(m.get_by_handle(&four), Some(&4));
+        assert_both_ways(&m, &[0, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cursor_at_wrong_index() {
+        let mut m: GList<u32> = (0..4).collect();
+        let mut cursor = m.cursor_back_mut();
+        let three = cursor.current_handle().unwrap();
+        m.cursor_mut_at(&three, 1);
+    }
+
+    #[test]
+    fn test_handles_stale_after_reuse() {
+        // A node freed with a handle on it is recycled right away, and the
+        // old handle still doesn't match whatever lands there
+        let mut m: GList<u32> = GList::with_node_pool(1);
+        let one = m.push_back_handle(1);
+        let addr = m.back().unwrap() as *const u32;
+        assert_eq!(m.pop_back(), Some(1));
+        assert_eq!(m.pool_stats().cached, 1);
+        let two = m.push_back_handle(2);
+        assert_eq!(m.back().unwrap() as *const u32, addr);
+        assert_eq!(m.get_by_handle(&one), None);
+        assert_eq!(m.remove_handle(&one), None);
+        assert_eq!(m.get_by_handle(&two), Some(&2));
+
+        // Handled nodes don't hang around past what the pool allows
+        let handles: Vec<_> = (0..10).map(|i| m.push_back_handle(i)).collect();
+        m.clear();
+        assert_eq!(m.pool_stats().cached, 1);
+        assert!(handles.iter().all(|handle| m.get_by_handle(handle).is_none()));
+
+        m.push_back(5);
+        let mut cursor = m.cursor_mut();
+        cursor.move_next();
+        let five = cursor.current_handle().unwrap();
+        assert_eq!(cursor.current_handle().unwrap().generation, five.generation);
+        assert_eq!(m.get_by_handle(&five), Some(&5));
+    }
+
+
+    #[test]
+    fn test_node_pool() {
+        let mut m: GList<u32> = GList::with_node_pool(2);
+        m.extend(0..4);
+        assert_eq!(m.pool_stats().allocated, 4);
+        assert_eq!(m.pool_stats().cached, 0);
+
+        m.pop_front();
+        m.pop_back();
+        m.pop_back();
+        // Only room for two spares, the third got freed
+        assert_eq!(m.pool_stats().cached, 2);
+        assert_both_ways(&m, &[1]);
+
+        m.push_front(10);
+        m.push_back(11);
+        m.push_back(12);
+        let stats = m.pool_stats();
+        assert_eq!(stats.reused, 2);
+        assert_eq!(stats.allocated, 5);
+        assert_eq!(stats.cached, 0);
+        assert_eq!(stats.max_cached, 2);
+        assert_both_ways(&m, &[10, 1, 11, 12]);
+
+        m.clear();
+        assert_eq!(m.pool_stats().cached, 2);
+        m.shrink_pool();
+        assert_eq!(m.pool_stats().cached, 0);
+        assert_eq!(m.pool_stats().max_cached, 2);
+    }
+
+    #[test]
+    fn test_node_pool_reuses_allocation() {
+        let mut m: GList<u32> = GList::with_node_pool(1);
         m.push_back(1);
-        assert!(n == m);
+        let before = m.front().unwrap() as *const u32;
+        m.pop_back();
+        m.push_front(2);
+        assert_eq!(m.front().unwrap() as *const u32, before);
+    }
 
-        let n = list_from(&[2, 3, 4]);
-        let m = list_from(&[1, 2, 3]);
-        assert!(n != m);
+    #[test]
+    fn test_node_pool_default_off() {
+        let mut m: GList<u32> = GList::new();
+        m.extend(0..3);
+        m.clear();
+        let stats = m.pool_stats();
+        assert_eq!(stats.cached, 0);
+        assert_eq!(stats.max_cached, 0);
+        assert_eq!(stats.reused, 0);
     }
 
     #[test]
-    fn test_ord() {
-        let n = list_from(&[]);
-        let m = list_from(&[1, 2, 3]);
-        assert!(n < m);
-        assert!(m > n);
-        assert!(n <= n);
-        assert!(n >= n);
+    fn test_node_pool_survives_take_all() {
+        // Moving the whole chain out shouldn't take the pool with it
+        let mut m: GList<u32> = GList::with_node_pool(4);
+        m.extend(0..3);
+        let mut n = GList::new();
+        n.append(&mut m);
+        assert_eq!(m.pool_stats().max_cached, 4);
+        let all = m.split_off(0);
+        assert!(all.is_empty());
+        assert_eq!(m.pool_stats().max_cached, 4);
     }
 
     #[test]
-    fn test_ord_nan() {
-        let nan = 0.0f64 / 0.0;
-        let n = list_from(&[nan]);
-        let m = list_from(&[nan]);
-        assert!(!(n < m));
-        assert!(!(n > m));
-        assert!(!(n <= m));
-        assert!(!(n >= m));
+    fn test_node_pool_drops_elems_once() {
+        use std::rc::Rc;
 
-        let n = list_from(&[nan]);
-        let one = list_from(&[1.0f64]);
-        assert!(!(n < one));
-        assert!(!(n > one));
-        assert!(!(n <= one));
-        assert!(!(n >= one));
+        let counter = Rc::new(());
+        {
+            let mut m = GList::with_node_pool(8);
+            for _ in 0..6 {
+                m.push_back(Rc::clone(&counter));
+            }
+            m.pop_front();
+            m.remove(2);
+            m.retain(|_| false);
+            assert_eq!(Rc::strong_count(&counter), 1);
+            m.push_back(Rc::clone(&counter));
+            assert_eq!(Rc::strong_count(&counter), 2);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
 
-        let u = list_from(&[1.0f64, 2.0, nan]);
-        let v = list_from(&[1.0f64, 2.0, 3.0]);
-        assert!(!(u < v));
-        assert!(!(u > v));
-        assert!(!(u <= v));
-        assert!(!(u >= v));
 
-        let s = list_from(&[1.0f64, 2.0, 4.0, 2.0]);
-        let t = list_from(&[1.0f64, 2.0, 3.0, 2.0]);
-        assert!(!(s < t));
-        assert!(s > one);
-        assert!(!(s <= one));
-        assert!(s >= one);
+    #[test]
+    fn test_send_sync() {
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+
+        is_send::<GList<String>>();
+        is_sync::<GList<String>>();
+        is_send::<super::Iter<String>>();
+        is_sync::<super::Iter<String>>();
+        is_send::<super::IterMut<String>>();
+        is_sync::<super::IterMut<String>>();
+        is_send::<super::IntoIter<String>>();
+        is_sync::<super::IntoIter<String>>();
+        is_send::<super::Cursor<String>>();
+        is_send::<super::CursorMut<String>>();
+        is_send::<super::Drain<String>>();
+        is_send::<super::NodeHandle<String>>();
+        is_send::<crate::lrucache::LruCache<String, String>>();
     }
 
     #[test]
-    fn test_debug() {
-        let list: GList<i32> = (0..10).collect();
-        assert_eq!(format!("{:?}", list), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]");
+    fn test_move_to_thread() {
+        let mut m: GList<String> = GList::new();
+        m.push_back("worker".to_string());
+        m.push_back("thread".to_string());
+
+        let m = std::thread::spawn(move || {
+            m.iter().map(|s| s.to_uppercase()).collect::<GList<String>>()
+        })
+        .join()
+        .unwrap();
+        assert_both_ways(&m, &["WORKER".to_string(), "THREAD".to_string()]);
+
+        // Shared between scoped threads for reading
+        let m: GList<u32> = (0..100).collect();
+        let sums: Vec<u32> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(|| m.iter().sum::<u32>())).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(sums, vec![4950; 4]);
+    }
+
+    // These only have to compile, they'd be type errors if
+    // GList or its iterators stopped being covariant
+    #[allow(dead_code)]
+    fn assert_covariance() {
+        fn a<'a>(x: GList<&'static str>) -> GList<&'a str> {
+            x
+        }
+        fn b<'i, 'a>(x: super::Iter<'i, &'static str>) -> super::Iter<'i, &'a str> {
+            x
+        }
+        fn c<'a>(x: super::IntoIter<&'static str>) -> super::IntoIter<&'a str> {
+            x
+        }
+        fn d<'c, 'a>(x: super::Cursor<'c, &'static str>) -> super::Cursor<'c, &'a str> {
+            x
+        }
+    }
 
-        let list: GList<&str> = vec!["just", "one", "test", "more"]
-            .iter().copied()
-            .collect();
-        assert_eq!(format!("{:?}", list), r#"["just", "one", "test", "more"]"#);
+
+    #[test]
+    #[should_panic(expected = "len is 5")]
+    fn test_invariants_bad_len() {
+        let mut m: GList<u32> = (0..3).collect();
+        m.len = 5;
+        m.check_invariants();
     }
 
     #[test]
-    fn test_hashmap() {
-        // Check that HashMap works with this as a key
+    fn test_invariants_bad_links() {
+        let mut m: GList<u32> = (0..3).collect();
+        let middle = unsafe { (*m.front.unwrap().as_ptr()).back.unwrap() };
+
+        let report = |m: &GList<u32>| {
+            let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| m.check_invariants()))
+                .expect_err("broken list passed the check");
+            err.downcast_ref::<String>().cloned().unwrap()
+        };
+
+        // Break things, check they get reported, then put them back
+        // so Drop doesn't go walking a broken chain
+        unsafe {
+            (*middle.as_ptr()).front = None;
+            assert!(report(&m).contains("node 1 front link"));
+            (*middle.as_ptr()).front = m.front;
+        }
+
+        let back = m.back.take();
+        assert!(report(&m).contains("only one end is set"));
+        m.back = back;
+
+        m.check_invariants();
+    }
 
-        let list1: GList<i32> = (0..10).collect();
-        let list2: GList<i32> = (1..11).collect();
-        let mut map = std::collections::HashMap::new();
+    #[test]
+    fn test_invariants_hold() {
+        // A bit of everything, every step of which runs the checker in test builds
+        let mut m: GList<u32> = GList::with_node_pool(4);
+        let handle = m.push_back_handle(100);
+        m.extend(0..20);
+        m.sort();
+        m.retain(|x| x % 3 != 0);
+        m.move_to_back(&handle);
+        let mut tail = m.split_off(5);
+        m.drain(1..3);
+        tail.insert(2, 7);
+        m.append(&mut tail);
+        m.remove(0);
+        m.shrink_pool();
+        m.check_invariants();
+        tail.check_invariants();
+    }
 
-        assert_eq!(map.insert(list1.clone(), "list1"), None);
-        assert_eq!(map.insert(list2.clone(), "list2"), None);
 
-        assert_eq!(map.len(), 2);
+    #[test]
+    fn test_rotate() {
+        let expected: Vec<u32> = (0..7).collect();
+        for k in 0..=7 {
+            let mut m: GList<u32> = (0..7).collect();
+            m.rotate_left(k);
+            let mut v = expected.clone();
+            v.rotate_left(k);
+            assert_both_ways(&m, &v);
+
+            m.rotate_right(k);
+            assert_both_ways(&m, &expected);
+        }
 
-        assert_eq!(map.get(&list1), Some(&"list1"));
-        assert_eq!(map.get(&list2), Some(&"list2"));
+        let mut empty: GList<u32> = GList::new();
+        empty.rotate_left(0);
+        empty.rotate_right(0);
+        assert_both_ways(&empty, &[]);
 
-        assert_eq!(map.remove(&list1), Some("list1"));
-        assert_eq!(map.remove(&list2), Some("list2"));
+        // Rotating relinks, the nodes themselves stay put
+        let mut m: GList<u32> = (0..3).collect();
+        let first = m.front().unwrap() as *const u32;
+        m.rotate_left(1);
+        assert_eq!(m.back().unwrap() as *const u32, first);
+    }
 
-        assert!(map.is_empty());
+    #[test]
+    #[should_panic]
+    fn test_rotate_out_of_bounds() {
+        let mut m: GList<u32> = (0..3).collect();
+        m.rotate_right(4);
     }
 
 
-}
\ No newline at end of file
+}
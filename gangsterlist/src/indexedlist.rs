@@ -0,0 +1,746 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::ops::{Index, IndexMut};
+
+use crate::gangsterlist::{self, Cursor, CursorMut, GList, NodeHandle};
+
+/*
+* IndexedGList is a GList with a skip list bolted on top, so get / insert /
+* remove by position are O(log n) expected instead of a walk from the ends.
+* The plain GList doesn't know or care, everything extra lives in here.
+*
+* Every element flips coins for a tower height, about half get no tower at
+* all, a quarter get one level, and so on. Towers hold a NodeHandle to
+* their GList node and at each level link to the next tower of at least
+* that height, along with the span (how many positions forward that is).
+* Finding position i goes down the levels like any skip list, and the
+* last couple of steps are just a cursor walking the GList itself.
+*
+* Positions here count the header as 0, so element i is at position i + 1.
+*
+* To keep push / pop at the ends O(1) expected, spans that run off the
+* back aren't stored (the level's tail keeps its own position instead),
+* and the header's spans and the tails' positions are stored relative to
+* `lead`, which moves by one on every push_front / pop_front. That way
+* shoving something on the front shifts all of them at once for free.
+* Stuff in the middle still has to touch every level, that's the log n.
+*/
+
+const MAX_LEVEL: usize = 32;
+const HEADER: usize = 0;
+
+pub struct IndexedGList<T> {
+    list: GList<T>,
+    towers: Vec<Tower<T>>,
+    free: Vec<usize>,
+    // (tower, position) of the last tower on each level
+    tails: [(usize, usize); MAX_LEVEL],
+    height: usize,
+    lead: usize,
+    seed: u64,
+}
+
+struct Tower<T> {
+    // None for the header
+    handle: Option<NodeHandle<T>>,
+    levels: Vec<Level>,
+}
+
+#[derive(Clone, Copy)]
+struct Level {
+    next: Option<usize>,
+    prev: usize,
+    // Only means anything when next is Some
+    span: usize,
+}
+
+impl Level {
+    const EMPTY: Level = Level {
+        next: None,
+        prev: HEADER,
+        span: 0,
+    };
+}
+
+impl<T> IndexedGList<T> {
+
+    pub fn new() -> Self {
+        IndexedGList {
+            list: GList::new(),
+            towers: vec![Tower {
+                handle: None,
+                levels: vec![Level::EMPTY; MAX_LEVEL],
+            }],
+            free: Vec::new(),
+            tails: [(HEADER, 0); MAX_LEVEL],
+            height: 0,
+            lead: 0,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.list.front()
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.list.front_mut()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.list.back()
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.list.back_mut()
+    }
+
+    pub fn iter(&self) -> gangsterlist::Iter<'_, T> {
+        self.list.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> gangsterlist::IterMut<'_, T> {
+        self.list.iter_mut()
+    }
+
+    // The list underneath, for anything that doesn't move elements around
+    pub fn as_list(&self) -> &GList<T> {
+        &self.list
+    }
+
+    pub fn into_list(self) -> GList<T> {
+        self.list
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.towers.truncate(1);
+        self.towers[HEADER].levels.fill(Level::EMPTY);
+        self.free.clear();
+        self.tails = [(HEADER, 0); MAX_LEVEL];
+        self.height = 0;
+        self.lead = 0;
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        self.list.push_front(elem);
+        // Everything just moved back one, header spans and tails included
+        self.lead = self.lead.wrapping_add(1);
+
+        let height = self.random_height();
+        if height == 0 {
+            return;
+        }
+        self.grow(height);
+
+        let handle = self.list.cursor_front_mut().current_handle();
+        let x = self.alloc_tower(handle, height);
+        for l in 0..height {
+            let next = self.towers[HEADER].levels[l].next;
+            self.towers[x].levels[l] = Level {
+                next,
+                prev: HEADER,
+                span: next.map_or(0, |_| self.span(HEADER, l) - 1),
+            };
+            self.towers[HEADER].levels[l].next = Some(x);
+            self.set_span(HEADER, l, 1);
+            match next {
+                Some(next) => self.towers[next].levels[l].prev = x,
+                None => self.set_tail(l, x, 1),
+            }
+        }
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        self.list.push_back(elem);
+
+        let height = self.random_height();
+        if height == 0 {
+            return;
+        }
+        self.grow(height);
+
+        let pos = self.list.len();
+        let handle = self.list.cursor_back_mut().current_handle();
+        let x = self.alloc_tower(handle, height);
+        for l in 0..height {
+            let (tail, rank) = self.tail(l);
+            self.towers[tail].levels[l].next = Some(x);
+            self.set_span(tail, l, pos - rank);
+            self.towers[x].levels[l] = Level {
+                next: None,
+                prev: tail,
+                span: 0,
+            };
+            self.set_tail(l, x, pos);
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let elem = self.list.pop_front()?;
+        let first = self.towers[HEADER].levels[0].next;
+        let towered = self.height > 0 && first.is_some() && self.span(HEADER, 0) == 1;
+        self.lead = self.lead.wrapping_sub(1);
+
+        if let (true, Some(x)) = (towered, first) {
+            for l in 0..self.towers[x].levels.len() {
+                let level = self.towers[x].levels[l];
+                self.towers[HEADER].levels[l].next = level.next;
+                match level.next {
+                    Some(next) => {
+                        self.set_span(HEADER, l, level.span);
+                        self.towers[next].levels[l].prev = HEADER;
+                    }
+                    None => self.set_tail(l, HEADER, 0),
+                }
+            }
+            self.free_tower(x);
+        }
+        Some(elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let pos = self.list.len();
+        let elem = self.list.pop_back()?;
+
+        let (x, rank) = self.tail(0);
+        if self.height > 0 && x != HEADER && rank == pos {
+            for l in 0..self.towers[x].levels.len() {
+                let prev = self.towers[x].levels[l].prev;
+                let rank = pos - self.span(prev, l);
+                self.towers[prev].levels[l].next = None;
+                self.set_tail(l, prev, rank);
+            }
+            self.free_tower(x);
+        }
+        Some(elem)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let (towers, ranks) = self.find(index + 1);
+        self.cursor_from(towers[0], ranks[0], index + 1).current()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let (towers, ranks) = self.find(index + 1);
+        self.cursor_mut_from(towers[0], ranks[0], index + 1).into_current()
+    }
+
+    // Same rules as GList::insert, index == len is a push_back and past that panics
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len(), "Cannot insert at a nonexistent index");
+
+        if index == 0 {
+            return self.push_front(elem);
+        }
+        if index == self.len() {
+            return self.push_back(elem);
+        }
+
+        let height = self.random_height();
+        self.grow(height);
+
+        // The new element goes right after position index
+        let (update, ranks) = self.find(index);
+        let mut cursor = self.cursor_mut_from(update[0], ranks[0], index);
+        cursor.insert_after(elem);
+        let handle = if height > 0 {
+            cursor.move_next();
+            cursor.current_handle()
+        } else {
+            None
+        };
+
+        let x = (height > 0).then(|| self.alloc_tower(handle, height));
+        let pos = index + 1;
+        for l in 0..self.height {
+            let (u, rank) = (update[l], ranks[l]);
+            let next = self.towers[u].levels[l].next;
+            let span = next.map(|_| self.span(u, l));
+
+            if next.is_some() {
+                // The tail is somewhere past us, so it just got shoved back
+                let (tail, tail_rank) = self.tail(l);
+                self.set_tail(l, tail, tail_rank + 1);
+            }
+
+            match (x, span) {
+                (Some(x), _) if l < height => {
+                    self.towers[x].levels[l] = Level {
+                        next,
+                        prev: u,
+                        span: span.map_or(0, |span| rank + span - index),
+                    };
+                    self.towers[u].levels[l].next = Some(x);
+                    self.set_span(u, l, pos - rank);
+                    match next {
+                        Some(next) => self.towers[next].levels[l].prev = x,
+                        None => self.set_tail(l, x, pos),
+                    }
+                }
+                (_, Some(span)) => self.set_span(u, l, span + 1),
+                // Nothing after us on this level, nothing to bump
+                (_, None) => {}
+            }
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        if index == 0 {
+            return self.pop_front();
+        }
+        if index == self.len() - 1 {
+            return self.pop_back();
+        }
+
+        let pos = index + 1;
+        let (update, ranks) = self.find(index);
+        let elem = self
+            .cursor_mut_from(update[0], ranks[0], pos)
+            .remove_current()?;
+
+        // If the element had a tower, it's the very next one on the bottom level
+        let mut target = None;
+        if self.height > 0 {
+            if let Some(next) = self.towers[update[0]].levels[0].next {
+                if ranks[0] + self.span(update[0], 0) == pos {
+                    target = Some(next);
+                }
+            }
+        }
+
+        for l in 0..self.height {
+            let (u, rank) = (update[l], ranks[l]);
+            let Some(next) = self.towers[u].levels[l].next else {
+                continue;
+            };
+            let span = self.span(u, l);
+
+            if Some(next) == target {
+                let level = self.towers[next].levels[l];
+                self.towers[u].levels[l].next = level.next;
+                match level.next {
+                    Some(after) => {
+                        self.set_span(u, l, span + level.span - 1);
+                        self.towers[after].levels[l].prev = u;
+                        let (tail, tail_rank) = self.tail(l);
+                        self.set_tail(l, tail, tail_rank - 1);
+                    }
+                    None => self.set_tail(l, u, rank),
+                }
+            } else {
+                self.set_span(u, l, span - 1);
+                let (tail, tail_rank) = self.tail(l);
+                self.set_tail(l, tail, tail_rank - 1);
+            }
+        }
+
+        if let Some(x) = target {
+            self.free_tower(x);
+        }
+        Some(elem)
+    }
+
+    // For every level, the last tower at or before pos and where it is
+    fn find(&self, pos: usize) -> ([usize; MAX_LEVEL], [usize; MAX_LEVEL]) {
+        let mut update = [HEADER; MAX_LEVEL];
+        let mut ranks = [0; MAX_LEVEL];
+        let mut x = HEADER;
+        let mut rank = 0;
+
+        for l in (0..self.height).rev() {
+            while let Some(next) = self.towers[x].levels[l].next {
+                let span = self.span(x, l);
+                if rank + span > pos {
+                    break;
+                }
+                rank += span;
+                x = next;
+            }
+            update[l] = x;
+            ranks[l] = rank;
+        }
+
+        (update, ranks)
+    }
+
+    // Parks a cursor on the tower's node (or the ghost for the header)
+    // and walks the rest of the way to pos on foot
+    fn cursor_from(&self, tower: usize, rank: usize, pos: usize) -> Cursor<'_, T> {
+        let mut cursor = match self.towers[tower].handle {
+            Some(handle) => self
+                .list
+                .cursor_at(&handle, rank - 1)
+                .expect("Index tower lost its node"),
+            None => self.list.cursor(),
+        };
+        for _ in rank..pos {
+            cursor.move_next();
+        }
+        cursor
+    }
+
+    fn cursor_mut_from(&mut self, tower: usize, rank: usize, pos: usize) -> CursorMut<'_, T> {
+        let mut cursor = match self.towers[tower].handle {
+            Some(handle) => self
+                .list
+                .cursor_mut_at(&handle, rank - 1)
+                .expect("Index tower lost its node"),
+            None => self.list.cursor_mut(),
+        };
+        for _ in rank..pos {
+            cursor.move_next();
+        }
+        cursor
+    }
+
+    // The header's spans and the tails' positions are relative to lead,
+    // every other tower's spans are stored as is
+    fn span(&self, tower: usize, level: usize) -> usize {
+        let span = self.towers[tower].levels[level].span;
+        if tower == HEADER {
+            span.wrapping_add(self.lead)
+        } else {
+            span
+        }
+    }
+
+    fn set_span(&mut self, tower: usize, level: usize, span: usize) {
+        self.towers[tower].levels[level].span = if tower == HEADER {
+            span.wrapping_sub(self.lead)
+        } else {
+            span
+        };
+    }
+
+    fn tail(&self, level: usize) -> (usize, usize) {
+        match self.tails[level] {
+            (HEADER, _) => (HEADER, 0),
+            (tower, rank) => (tower, rank.wrapping_add(self.lead)),
+        }
+    }
+
+    fn set_tail(&mut self, level: usize, tower: usize, rank: usize) {
+        self.tails[level] = (tower, rank.wrapping_sub(self.lead));
+    }
+
+    // Flip coins until one comes up tails
+    fn random_height(&mut self) -> usize {
+        // xorshift64, plenty random enough for picking tower heights
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed.trailing_ones() as usize).min(MAX_LEVEL)
+    }
+
+    // New levels start out with nothing on them
+    fn grow(&mut self, height: usize) {
+        for l in self.height..height {
+            self.towers[HEADER].levels[l] = Level::EMPTY;
+            self.tails[l] = (HEADER, 0);
+        }
+        self.height = self.height.max(height);
+    }
+
+    fn alloc_tower(&mut self, handle: Option<NodeHandle<T>>, height: usize) -> usize {
+        let tower = Tower {
+            handle,
+            levels: vec![Level::EMPTY; height],
+        };
+
+        match self.free.pop() {
+            Some(x) => {
+                self.towers[x] = tower;
+                x
+            }
+            None => {
+                self.towers.push(tower);
+                self.towers.len() - 1
+            }
+        }
+    }
+
+    fn free_tower(&mut self, x: usize) {
+        self.towers[x].handle = None;
+        self.towers[x].levels = Vec::new();
+        self.free.push(x);
+    }
+
+    // Walks every level and checks the spans and links add up, and that
+    // every tower really sits on the node at its position
+    #[cfg(test)]
+    fn check_index(&self) {
+        let mut live = 0;
+
+        for l in 0..self.height {
+            let mut x = HEADER;
+            let mut pos = 0;
+            while let Some(next) = self.towers[x].levels[l].next {
+                assert_eq!(self.towers[next].levels[l].prev, x, "bad prev on level {}", l);
+                pos += self.span(x, l);
+                assert!(pos <= self.len(), "span runs off the end on level {}", l);
+
+                let handle = self.towers[next].handle.expect("tower without a node");
+                let elem = self.list.get_by_handle(&handle).expect("tower with a stale handle");
+                assert!(std::ptr::eq(elem, self.list.get(pos - 1).unwrap()), "tower in the wrong place on level {}", l);
+
+                if l == 0 {
+                    live += 1;
+                }
+                x = next;
+            }
+            assert_eq!(self.tail(l), (x, pos), "bad tail on level {}", l);
+        }
+
+        assert_eq!(live, self.towers.len() - 1 - self.free.len(), "leaked towers");
+    }
+}
+
+impl<T> Default for IndexedGList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<GList<T>> for IndexedGList<T> {
+    fn from(list: GList<T>) -> Self {
+        list.into_iter().collect()
+    }
+}
+
+impl<T: Clone> Clone for IndexedGList<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> Extend<T> for IndexedGList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for IndexedGList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = IndexedGList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug> Debug for IndexedGList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.list.fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for IndexedGList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.list == other.list
+    }
+}
+
+impl<T: Eq> Eq for IndexedGList<T> {}
+
+impl<T: Hash> Hash for IndexedGList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.list.hash(state);
+    }
+}
+
+impl<T> Index<usize> for IndexedGList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("Out of bounds access")
+    }
+}
+
+impl<T> IndexMut<usize> for IndexedGList<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("Out of bounds access")
+    }
+}
+
+impl<'a, T> IntoIterator for &'a IndexedGList<T> {
+    type IntoIter = gangsterlist::Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for IndexedGList<T> {
+    type IntoIter = gangsterlist::IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.into_iter()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::IndexedGList;
+    use crate::gangsterlist::GList;
+
+    // Same little LCG the GList tests use
+    fn pseudo_random(seed: &mut u64) -> usize {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (*seed >> 33) as usize
+    }
+
+    fn assert_matches(list: &IndexedGList<u32>, model: &[u32]) {
+        list.check_index();
+        assert_eq!(list.len(), model.len());
+        assert!(list.iter().eq(model.iter()));
+        for (i, elem) in model.iter().enumerate() {
+            assert_eq!(list.get(i), Some(elem));
+        }
+        assert_eq!(list.get(model.len()), None);
+    }
+
+    #[test]
+    fn basics() {
+        let mut list = IndexedGList::new();
+        assert!(list.is_empty());
+        assert_eq!(list.get(0), None);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.remove(0), None);
+
+        for i in 0..10 {
+            list.push_back(i);
+        }
+        list.insert(3, 100);
+        list.insert(0, 200);
+        list.insert(12, 300);
+        assert_matches(&list, &[200, 0, 1, 2, 100, 3, 4, 5, 6, 7, 8, 9, 300]);
+
+        assert_eq!(list.remove(4), Some(100));
+        assert_eq!(list.remove(0), Some(200));
+        assert_eq!(list.remove(10), Some(300));
+        assert_eq!(list.remove(10), None);
+        assert_matches(&list, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+        list[5] *= 10;
+        *list.get_mut(0).unwrap() = 42;
+        assert_eq!(list[5], 50);
+        assert_eq!(list.front(), Some(&42));
+        assert_eq!(list.back(), Some(&9));
+
+        list.clear();
+        assert_matches(&list, &[]);
+        list.push_front(1);
+        assert_matches(&list, &[1]);
+    }
+
+    #[test]
+    fn matches_vec() {
+        let mut list = IndexedGList::new();
+        let mut model = Vec::new();
+        let mut seed = 7;
+
+        for round in 0..4000 {
+            let value = round as u32;
+            match pseudo_random(&mut seed) % 8 {
+                0 => {
+                    list.push_front(value);
+                    model.insert(0, value);
+                }
+                1 => {
+                    list.push_back(value);
+                    model.push(value);
+                }
+                2 => assert_eq!(list.pop_front(), (!model.is_empty()).then(|| model.remove(0))),
+                3 => assert_eq!(list.pop_back(), model.pop()),
+                4 | 5 => {
+                    let index = pseudo_random(&mut seed) % (model.len() + 1);
+                    list.insert(index, value);
+                    model.insert(index, value);
+                }
+                _ => {
+                    if !model.is_empty() {
+                        let index = pseudo_random(&mut seed) % model.len();
+                        assert_eq!(list.remove(index), Some(model.remove(index)));
+                    }
+                }
+            }
+
+            if round % 97 == 0 {
+                assert_matches(&list, &model);
+            }
+        }
+        assert_matches(&list, &model);
+
+        // Drain it from the middle out
+        while !model.is_empty() {
+            let index = model.len() / 2;
+            assert_eq!(list.remove(index), Some(model.remove(index)));
+        }
+        assert_matches(&list, &model);
+    }
+
+    #[test]
+    fn middle_inserts() {
+        // Tests check the GList invariants on every mutation, so keep it smallish
+        let mut list: IndexedGList<u32> = (0..1000).collect();
+        for i in (0..1000).step_by(10) {
+            list.insert(500, i);
+        }
+        assert_eq!(list.len(), 1100);
+        assert_eq!(list[500], 990);
+        assert_eq!(list[599], 0);
+        assert_eq!(list[600], 500);
+        list.check_index();
+        for i in 0..100 {
+            assert_eq!(list.remove(500), Some(990 - i * 10));
+        }
+        for i in (0..1000).step_by(37) {
+            assert_eq!(list[i as usize], i);
+        }
+        list.check_index();
+    }
+
+    #[test]
+    fn traits() {
+        let plain: GList<u32> = (0..20).collect();
+        let list = IndexedGList::from(plain.clone());
+        let copy = list.clone();
+        assert_eq!(list, copy);
+        assert_eq!(list.as_list(), &plain);
+        assert_eq!(format!("{:?}", copy), format!("{:?}", plain));
+
+        let mut list = list;
+        list.extend(20..25);
+        for elem in list.iter_mut() {
+            *elem += 1;
+        }
+        assert_eq!(list.into_iter().collect::<Vec<_>>(), (1..26).collect::<Vec<_>>());
+    }
+}
@@ -1 +1,11 @@
-pub mod gangsterlist;
\ No newline at end of file
+pub mod arenalist;
+pub mod circularlist;
+pub mod concurrentlist;
+pub mod gangsterlist;
+pub mod indexedlist;
+pub mod linkedhash;
+pub mod lrucache;
+pub mod sortedlist;
+pub mod textbuffer;
+pub mod undolist;
+pub mod unrolledlist;
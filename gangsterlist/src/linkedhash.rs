@@ -0,0 +1,870 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::mem;
+use std::ops::Index;
+
+use crate::gangsterlist::{self, GList, NodeHandle};
+
+/*
+* LinkedHashMap is a HashMap that remembers the order things went in, and
+* LinkedHashSet is the same thing without values.
+*
+* It's built like LruCache: the entries live in a GList in insertion order,
+* and a HashMap points every key at its node through a NodeHandle, so
+* lookups, removes and reorders never walk the list.
+*
+* insert() on a key that's already there swaps the value and leaves the
+* entry where it was. insert_to_back() swaps it and moves the entry to the
+* back, as if it had just gone in. Pick whichever your ordering wants.
+*
+* remove() is O(1), it takes the node out through its handle.
+* front() / back() / pop_front() / pop_back() are the oldest and newest entries.
+* iter(), iter_mut(), keys() and values() all go oldest to newest.
+*
+* entry() works like HashMap's. A vacant entry goes in at the back.
+*
+* Keys are stored twice (once in the map, once in the node, so popping
+* knows what to take out of the map), hence K: Clone.
+*/
+
+pub struct LinkedHashMap<K, V> {
+    list: GList<(K, V)>,
+    map: HashMap<K, NodeHandle<(K, V)>>,
+}
+
+pub struct LinkedHashSet<T> {
+    map: LinkedHashMap<T, ()>,
+}
+
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    map: &'a mut LinkedHashMap<K, V>,
+    handle: NodeHandle<(K, V)>,
+}
+
+pub struct VacantEntry<'a, K, V> {
+    map: &'a mut LinkedHashMap<K, V>,
+    key: K,
+}
+
+pub struct Iter<'a, K, V> {
+    inner: gangsterlist::Iter<'a, (K, V)>,
+}
+
+pub struct IterMut<'a, K, V> {
+    inner: gangsterlist::IterMut<'a, (K, V)>,
+}
+
+pub struct Keys<'a, K, V> {
+    inner: gangsterlist::Iter<'a, (K, V)>,
+}
+
+pub struct Values<'a, K, V> {
+    inner: gangsterlist::Iter<'a, (K, V)>,
+}
+
+pub struct IntoIter<K, V> {
+    inner: gangsterlist::IntoIter<(K, V)>,
+}
+
+impl<K: Hash + Eq + Clone, V> LinkedHashMap<K, V> {
+
+    pub fn new() -> Self {
+        LinkedHashMap {
+            list: GList::new(),
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        LinkedHashMap {
+            list: GList::new(),
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.get(key)?;
+        self.list.get_by_handle(handle).map(|(_, value)| value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.get(key)?;
+        self.list.get_by_handle_mut(handle).map(|(_, value)| value)
+    }
+
+    // A key that's already there keeps its place, only the value changes.
+    // Hands back the old value if there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    // Same as insert, except a key that's already there moves to the back
+    pub fn insert_to_back(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.insert(key.clone(), value);
+        if old.is_some() {
+            self.move_to_back(&key);
+        }
+        old
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.remove(key)?;
+        self.list.remove_handle(&handle)
+    }
+
+    // Returns false if the key isn't there
+    pub fn move_to_front<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.map.get(key) {
+            Some(handle) => self.list.move_to_front(handle),
+            None => false,
+        }
+    }
+
+    pub fn move_to_back<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.map.get(key) {
+            Some(handle) => self.list.move_to_back(handle),
+            None => false,
+        }
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.map.get(&key) {
+            Some(&handle) => Entry::Occupied(OccupiedEntry { map: self, handle }),
+            None => Entry::Vacant(VacantEntry { map: self, key }),
+        }
+    }
+
+    // The oldest entry
+    pub fn front(&self) -> Option<(&K, &V)> {
+        self.list.front().map(|(key, value)| (key, value))
+    }
+
+    pub fn front_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.list.front_mut().map(|(key, value)| (&*key, value))
+    }
+
+    // The newest entry
+    pub fn back(&self) -> Option<(&K, &V)> {
+        self.list.back().map(|(key, value)| (key, value))
+    }
+
+    pub fn back_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.list.back_mut().map(|(key, value)| (&*key, value))
+    }
+
+    pub fn pop_front(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_front()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    pub fn pop_back(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_back()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    // Keeps the ones f says yes to, in the same order
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let map = &mut self.map;
+        self.list.retain_mut(|(key, value)| {
+            let keep = f(key, value);
+            if !keep {
+                map.remove(key);
+            }
+            keep
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.list.clear();
+    }
+}
+
+impl<K, V> LinkedHashMap<K, V> {
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.list.iter(),
+        }
+    }
+
+    // Only the values can change, the keys are still in the map
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            inner: self.list.iter_mut(),
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys {
+            inner: self.list.iter(),
+        }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values {
+            inner: self.list.iter(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Default for LinkedHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Handles only point into their own list, so build the copy from scratch
+impl<K: Hash + Eq + Clone, V: Clone> Clone for LinkedHashMap<K, V> {
+    fn clone(&self) -> Self {
+        self.iter().map(|(key, value)| (key.clone(), value.clone())).collect()
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for LinkedHashMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Order matters, same entries in a different order aren't equal
+impl<K: PartialEq, V: PartialEq> PartialEq for LinkedHashMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.list == other.list
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for LinkedHashMap<K, V> {}
+
+impl<K: Hash + Eq + Clone, V> Extend<(K, V)> for LinkedHashMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> FromIterator<(K, V)> for LinkedHashMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = LinkedHashMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, Q, V> Index<&Q> for LinkedHashMap<K, V>
+where
+    K: Hash + Eq + Clone + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &Self::Output {
+        self.get(key).expect("Key not in the LinkedHashMap")
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a LinkedHashMap<K, V> {
+    type IntoIter = Iter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut LinkedHashMap<K, V> {
+    type IntoIter = IterMut<'a, K, V>;
+    type Item = (&'a K, &'a mut V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V> IntoIterator for LinkedHashMap<K, V> {
+    type IntoIter = IntoIter<K, V>;
+    type Item = (K, V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.list.into_iter(),
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V> Entry<'a, K, V> {
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V> OccupiedEntry<'a, K, V> {
+
+    pub fn key(&self) -> &K {
+        &self.entry().0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.entry().1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.list.get_by_handle_mut(&self.handle).unwrap().1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.list.get_by_handle_mut(&self.handle).unwrap().1
+    }
+
+    // Swaps the value, the entry stays where it is
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let (key, value) = self.map.list.remove_handle(&self.handle).unwrap();
+        self.map.map.remove(&key);
+        (key, value)
+    }
+
+    // The entry was found through the map, so the handle is always good
+    fn entry(&self) -> &(K, V) {
+        self.map.list.get_by_handle(&self.handle).unwrap()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V> VacantEntry<'a, K, V> {
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    // Goes in at the back
+    pub fn insert(self, value: V) -> &'a mut V {
+        let handle = self.map.list.push_back_handle((self.key.clone(), value));
+        self.map.map.insert(self.key, handle);
+        &mut self.map.list.back_mut().unwrap().1
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| (&*key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, value)| (&*key, value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<T: Hash + Eq + Clone> LinkedHashSet<T> {
+
+    pub fn new() -> Self {
+        LinkedHashSet {
+            map: LinkedHashMap::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        LinkedHashSet {
+            map: LinkedHashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q>(&self, elem: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(elem)
+    }
+
+    // Returns false if it was already there, it keeps its place then
+    pub fn insert(&mut self, elem: T) -> bool {
+        self.map.insert(elem, ()).is_none()
+    }
+
+    // Same, but an element that's already there moves to the back
+    pub fn insert_to_back(&mut self, elem: T) -> bool {
+        self.map.insert_to_back(elem, ()).is_none()
+    }
+
+    pub fn remove<Q>(&mut self, elem: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove(elem).is_some()
+    }
+
+    pub fn take<Q>(&mut self, elem: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove_entry(elem).map(|(elem, _)| elem)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.map.front().map(|(elem, _)| elem)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.map.back().map(|(elem, _)| elem)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.map.pop_front().map(|(elem, _)| elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.map.pop_back().map(|(elem, _)| elem)
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.map.retain(|elem, _| f(elem));
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+
+impl<T> LinkedHashSet<T> {
+
+    pub fn iter(&self) -> Keys<'_, T, ()> {
+        self.map.keys()
+    }
+}
+
+impl<T: Hash + Eq + Clone> Default for LinkedHashSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash + Eq + Clone> Clone for LinkedHashSet<T> {
+    fn clone(&self) -> Self {
+        LinkedHashSet {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for LinkedHashSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for LinkedHashSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Eq> Eq for LinkedHashSet<T> {}
+
+impl<T: Hash + Eq + Clone> Extend<T> for LinkedHashSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.insert(elem);
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> FromIterator<T> for LinkedHashSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = LinkedHashSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedHashSet<T> {
+    type IntoIter = Keys<'a, T, ()>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for LinkedHashSet<T> {
+    type IntoIter = std::iter::Map<IntoIter<T, ()>, fn((T, ())) -> T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter().map(|(elem, _)| elem)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::{Entry, LinkedHashMap, LinkedHashSet};
+
+    fn keys(map: &LinkedHashMap<&'static str, u32>) -> Vec<&'static str> {
+        map.keys().copied().collect()
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut map = LinkedHashMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("b", 1), None);
+        assert_eq!(map.insert("a", 2), None);
+        assert_eq!(map.insert("c", 3), None);
+        assert_eq!(keys(&map), vec!["b", "a", "c"]);
+
+        // Keeps its place
+        assert_eq!(map.insert("b", 10), Some(1));
+        assert_eq!(keys(&map), vec!["b", "a", "c"]);
+        // Moves to the back
+        assert_eq!(map.insert_to_back("a", 20), Some(2));
+        assert_eq!(keys(&map), vec!["b", "c", "a"]);
+        assert_eq!(map.insert_to_back("d", 4), None);
+
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("a"), Some(&20));
+        assert_eq!(map["b"], 10);
+        assert!(map.contains_key("c"));
+        assert!(!map.contains_key("z"));
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), vec![10, 3, 20, 4]);
+        assert_eq!(format!("{:?}", map), r#"{"b": 10, "c": 3, "a": 20, "d": 4}"#);
+    }
+
+    #[test]
+    fn removing_and_ends() {
+        let mut map: LinkedHashMap<_, _> = ["a", "b", "c", "d", "e"].into_iter().zip(0..).collect();
+
+        assert_eq!(map.remove("c"), Some(2));
+        assert_eq!(map.remove("c"), None);
+        assert_eq!(map.remove_entry("a"), Some(("a", 0)));
+        assert_eq!(keys(&map), vec!["b", "d", "e"]);
+
+        assert_eq!(map.front(), Some((&"b", &1)));
+        assert_eq!(map.back(), Some((&"e", &4)));
+        *map.back_mut().unwrap().1 += 10;
+        assert_eq!(map.pop_back(), Some(("e", 14)));
+        assert_eq!(map.pop_front(), Some(("b", 1)));
+        assert!(!map.contains_key("b"));
+
+        assert!(map.move_to_front("d"));
+        assert!(!map.move_to_back("nope"));
+        map.insert("f", 5);
+        map.insert("g", 6);
+        map.retain(|_, value| *value % 2 == 1);
+        assert_eq!(keys(&map), vec!["d", "f"]);
+
+        map.clear();
+        assert_eq!(map.pop_front(), None);
+        map.insert("a", 1);
+        assert_eq!(keys(&map), vec!["a"]);
+    }
+
+    #[test]
+    fn entries() {
+        let mut counts: LinkedHashMap<char, u32> = LinkedHashMap::new();
+        for c in "hello world".chars().filter(|c| c.is_alphabetic()) {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        let seen: Vec<_> = counts.iter().map(|(c, n)| (*c, *n)).collect();
+        assert_eq!(
+            seen,
+            vec![('h', 1), ('e', 1), ('l', 3), ('o', 2), ('w', 1), ('r', 1), ('d', 1)]
+        );
+
+        counts.entry('h').and_modify(|n| *n += 100).or_default();
+        counts.entry('z').and_modify(|n| *n += 100).or_default();
+        assert_eq!(counts[&'h'], 101);
+        assert_eq!(counts.back(), Some((&'z', &0)));
+
+        match counts.entry('l') {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &'l');
+                assert_eq!(entry.insert(30), 3);
+                assert_eq!(entry.get(), &30);
+                assert_eq!(entry.remove_entry(), ('l', 30));
+            }
+            Entry::Vacant(_) => panic!("l should be there"),
+        }
+        match counts.entry('q') {
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &'q');
+                *entry.insert(7) += 1;
+            }
+            Entry::Occupied(_) => panic!("q shouldn't be there"),
+        }
+        assert_eq!(counts.get(&'l'), None);
+        assert_eq!(counts.back(), Some((&'q', &8)));
+        assert_eq!(counts.len(), 8);
+    }
+
+    #[test]
+    fn map_traits() {
+        let mut map: LinkedHashMap<String, Vec<u32>> = LinkedHashMap::new();
+        map.insert("one".to_string(), vec![1]);
+        map.insert("two".to_string(), vec![2]);
+        for (_, value) in &mut map {
+            value.push(0);
+        }
+        assert_eq!(map.get("two"), Some(&vec![2, 0]));
+
+        let copy = map.clone();
+        assert_eq!(copy, map);
+        map.remove("one");
+        map.insert("one".to_string(), vec![1, 0]);
+        // Same entries, different order
+        assert_ne!(copy, map);
+
+        let owned: Vec<_> = map.into_iter().rev().map(|(key, _)| key).collect();
+        assert_eq!(owned, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn sets() {
+        let mut set: LinkedHashSet<u32> = [5, 3, 5, 1, 3].into_iter().collect();
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), vec![5, 3, 1]);
+        assert_eq!(set.len(), 3);
+
+        assert!(!set.insert(5));
+        assert!(set.insert(7));
+        assert!(!set.insert_to_back(5));
+        assert_eq!(format!("{:?}", set), "{3, 1, 7, 5}");
+
+        assert!(set.contains(&1));
+        assert!(set.remove(&1));
+        assert!(!set.remove(&1));
+        assert_eq!(set.take(&7), Some(7));
+        assert_eq!(set.front(), Some(&3));
+        assert_eq!(set.back(), Some(&5));
+
+        let copy = set.clone();
+        assert_eq!(set.pop_front(), Some(3));
+        assert_eq!(set.pop_back(), Some(5));
+        assert_eq!(set.pop_back(), None);
+        assert!(set.is_empty());
+        assert_eq!(copy.into_iter().collect::<Vec<_>>(), vec![3, 5]);
+    }
+}
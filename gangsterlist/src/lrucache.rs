@@ -0,0 +1,298 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::gangsterlist::{self, GList, NodeHandle};
+
+/*
+* LruCache is a fixed capacity map that forgets whatever was used least recently.
+*
+* The entries live in a GList ordered by recency, most recent at the front,
+* and a HashMap points every key at its node through a NodeHandle.
+* That way getting, promoting and evicting never walk the list:
+*
+* get() finds the node through the map and relinks it at the front.
+* peek() finds it without promoting it.
+* put() inserts or updates at the front and evicts off the back when full.
+* pop_lru() evicts off the back on demand.
+* resize() changes the capacity, evicting as many as it has to.
+*
+* iter() walks from most to least recently used.
+*
+* Keys are stored twice (once in the map, once in the node, so eviction
+* knows what to take out of the map), hence K: Clone.
+*/
+
+pub struct LruCache<K, V> {
+    list: GList<(K, V)>,
+    map: HashMap<K, NodeHandle<(K, V)>>,
+    capacity: usize,
+}
+
+pub struct Iter<'a, K, V> {
+    inner: gangsterlist::Iter<'a, (K, V)>,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "LruCache needs room for at least one entry");
+
+        LruCache {
+            list: GList::new(),
+            map: HashMap::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    // Marks the entry as most recently used
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.get(key)?;
+        self.list.move_to_front(handle);
+        self.list.get_by_handle_mut(handle).map(|(_, value)| value)
+    }
+
+    // Looks without touching the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.get(key)?;
+        self.list.get_by_handle(handle).map(|(_, value)| value)
+    }
+
+    // The entry that'll get evicted next
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.list.back().map(|(key, value)| (key, value))
+    }
+
+    // Inserts or updates the entry and makes it the most recent.
+    // Hands back the old value if the key was already there.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(handle) = self.map.get(&key) {
+            self.list.move_to_front(handle);
+            let (_, old) = self.list.get_by_handle_mut(handle)?;
+            return Some(std::mem::replace(old, value));
+        }
+
+        if self.list.len() == self.capacity {
+            self.pop_lru();
+        }
+
+        let handle = self.list.push_front_handle((key.clone(), value));
+        self.map.insert(key, handle);
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.remove(key)?;
+        self.list.remove_handle(&handle).map(|(_, value)| value)
+    }
+
+    // Evicts the least recently used entry
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_back()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    // Shrinking evicts from the back until everything fits
+    pub fn resize(&mut self, capacity: usize) {
+        assert!(capacity > 0, "LruCache needs room for at least one entry");
+
+        while self.list.len() > capacity {
+            self.pop_lru();
+        }
+        self.capacity = capacity;
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.list.clear();
+    }
+
+    // Most recently used first
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.list.iter(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for LruCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.list.iter().map(|(key, value)| (key, value)))
+            .finish()
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V> IntoIterator for &'a LruCache<K, V> {
+    type IntoIter = Iter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::LruCache;
+
+    fn keys(cache: &LruCache<&'static str, u32>) -> Vec<&'static str> {
+        cache.iter().map(|(key, _)| *key).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut cache = LruCache::new(2);
+        assert!(cache.is_empty());
+        assert_eq!(cache.put("apple", 1), None);
+        assert_eq!(cache.put("banana", 2), None);
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.get("apple"), Some(&1));
+        assert_eq!(cache.get("banana"), Some(&2));
+        assert_eq!(cache.get("cherry"), None);
+
+        // banana was used last, so apple goes
+        assert_eq!(cache.put("cherry", 3), None);
+        assert_eq!(cache.get("apple"), None);
+        assert_eq!(keys(&cache), vec!["cherry", "banana"]);
+
+        assert_eq!(cache.put("banana", 20), Some(2));
+        assert_eq!(keys(&cache), vec!["banana", "cherry"]);
+        assert_eq!(cache.len(), 2);
+
+        *cache.get_mut("cherry").unwrap() += 30;
+        assert_eq!(cache.peek("cherry"), Some(&33));
+    }
+
+    #[test]
+    fn peek_doesnt_promote() {
+        let mut cache = LruCache::new(2);
+        cache.put("apple", 1);
+        cache.put("banana", 2);
+
+        assert_eq!(cache.peek("apple"), Some(&1));
+        assert_eq!(cache.peek_lru(), Some((&"apple", &1)));
+        cache.put("cherry", 3);
+        assert!(!cache.contains("apple"));
+        assert!(cache.contains("banana"));
+    }
+
+    #[test]
+    fn pop_remove_resize() {
+        let mut cache = LruCache::new(4);
+        for (i, key) in ["a", "b", "c", "d"].iter().enumerate() {
+            cache.put(*key, i as u32);
+        }
+        assert_eq!(keys(&cache), vec!["d", "c", "b", "a"]);
+
+        assert_eq!(cache.pop_lru(), Some(("a", 0)));
+        assert_eq!(cache.remove("c"), Some(2));
+        assert_eq!(cache.remove("c"), None);
+        assert_eq!(keys(&cache), vec!["d", "b"]);
+
+        cache.put("e", 4);
+        cache.put("f", 5);
+        cache.get("b");
+        cache.resize(2);
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(keys(&cache), vec!["b", "f"]);
+        assert!(!cache.contains("d"));
+
+        cache.resize(3);
+        cache.put("g", 6);
+        assert_eq!(keys(&cache), vec!["g", "b", "f"]);
+        assert_eq!(cache.iter().next_back(), Some((&"f", &5)));
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.pop_lru(), None);
+        cache.put("h", 7);
+        assert_eq!(format!("{:?}", cache), r#"{"h": 7}"#);
+    }
+
+    #[test]
+    fn owned_keys() {
+        let mut cache: LruCache<String, Vec<u8>> = LruCache::new(3);
+        cache.put("one".to_string(), vec![1]);
+        cache.put("two".to_string(), vec![2, 2]);
+        assert_eq!(cache.get("one").map(|v| v.len()), Some(1));
+        assert_eq!(cache.remove("two"), Some(vec![2, 2]));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_capacity() {
+        let _cache: LruCache<u32, u32> = LruCache::new(0);
+    }
+}
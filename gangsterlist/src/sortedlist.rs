@@ -0,0 +1,334 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Debug;
+use std::ops::{Bound, RangeBounds};
+
+use crate::gangsterlist::{self, Cursor, GList};
+
+/*
+* SortedGListBy is a GList that keeps itself sorted by a comparator,
+* and SortedGList is the same thing using T's own Ord.
+*
+* insert() scans in from the back, so feeding it things that are already
+* (mostly) in order is O(1) each. Equal elements keep the order they
+* were inserted in, a new one always lands behind the ones it's equal to.
+*
+* first() / last() / pop_first() / pop_last() are just the list's ends.
+* range(lo..hi) walks everything between two bounds, in order.
+* remove(&x) takes out the first element equal to x.
+*
+* Building one from an unsorted iterator collects everything first and then
+* merge sorts it once, O(n log n), instead of doing n separate inserts.
+*/
+
+pub struct SortedGListBy<T, F> {
+    list: GList<T>,
+    compare: F,
+}
+
+pub type SortedGList<T> = SortedGListBy<T, fn(&T, &T) -> Ordering>;
+
+pub struct Range<'a, T, F, R> {
+    cursor: Cursor<'a, T>,
+    compare: &'a F,
+    range: R,
+}
+
+impl<T: Ord> SortedGList<T> {
+    pub fn new() -> Self {
+        SortedGListBy::new_by(T::cmp)
+    }
+}
+
+impl<T: Ord> Default for SortedGList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, F> SortedGListBy<T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+
+    pub fn new_by(compare: F) -> Self {
+        SortedGListBy {
+            list: GList::new(),
+            compare,
+        }
+    }
+
+    // Collect everything, then sort the lot in one go
+    pub fn from_iter_by<I: IntoIterator<Item = T>>(iter: I, compare: F) -> Self {
+        let mut list: GList<T> = iter.into_iter().collect();
+        list.sort_by(&compare);
+        SortedGListBy { list, compare }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // Lands behind everything it's equal to, so equal elements stay
+    // in insertion order
+    pub fn insert(&mut self, elem: T) {
+        let mut cursor = self.list.cursor_back_mut();
+        while let Some(cur) = cursor.current() {
+            if (self.compare)(cur, &elem) != Ordering::Greater {
+                break;
+            }
+            cursor.move_prev();
+        }
+        // Walked off the front onto the ghost? Then this is a push_front
+        cursor.insert_after(elem);
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.list.front()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.list.back()
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+
+    pub fn contains(&self, elem: &T) -> bool {
+        self.iter()
+            .take_while(|cur| (self.compare)(cur, elem) != Ordering::Greater)
+            .any(|cur| (self.compare)(cur, elem) == Ordering::Equal)
+    }
+
+    // Takes out the first element that compares equal to elem
+    pub fn remove(&mut self, elem: &T) -> Option<T> {
+        let mut cursor = self.list.cursor_front_mut();
+        while let Some(cur) = cursor.current() {
+            match (self.compare)(cur, elem) {
+                Ordering::Less => cursor.move_next(),
+                Ordering::Equal => return cursor.remove_current(),
+                // Everything from here on is bigger, it's not in here
+                Ordering::Greater => return None,
+            }
+        }
+        None
+    }
+
+    // Everything inside the bounds, smallest first
+    pub fn range<R>(&self, range: R) -> Range<'_, T, F, R>
+    where
+        R: RangeBounds<T>,
+    {
+        let mut cursor = self.list.cursor_front();
+        while let Some(cur) = cursor.current() {
+            let below = match range.start_bound() {
+                Bound::Included(start) => (self.compare)(cur, start) == Ordering::Less,
+                Bound::Excluded(start) => (self.compare)(cur, start) != Ordering::Greater,
+                Bound::Unbounded => false,
+            };
+            if !below {
+                break;
+            }
+            cursor.move_next();
+        }
+
+        Range {
+            cursor,
+            compare: &self.compare,
+            range,
+        }
+    }
+
+    pub fn iter(&self) -> gangsterlist::Iter<'_, T> {
+        self.list.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    pub fn as_list(&self) -> &GList<T> {
+        &self.list
+    }
+
+    pub fn into_list(self) -> GList<T> {
+        self.list
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SortedGList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        SortedGListBy::from_iter_by(iter, T::cmp)
+    }
+}
+
+impl<T: Clone, F: Clone> Clone for SortedGListBy<T, F> {
+    fn clone(&self) -> Self {
+        SortedGListBy {
+            list: self.list.clone(),
+            compare: self.compare.clone(),
+        }
+    }
+}
+
+impl<T: Debug, F> Debug for SortedGListBy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.list.fmt(f)
+    }
+}
+
+impl<'a, T, F> IntoIterator for &'a SortedGListBy<T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    type IntoIter = gangsterlist::Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, F> IntoIterator for SortedGListBy<T, F> {
+    type IntoIter = gangsterlist::IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.into_iter()
+    }
+}
+
+impl<'a, T, F, R> Iterator for Range<'a, T, F, R>
+where
+    F: Fn(&T, &T) -> Ordering,
+    R: RangeBounds<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Once we're on the ghost we're done, moving on would wrap to the front
+        let cur = self.cursor.current()?;
+
+        let past_end = match self.range.end_bound() {
+            Bound::Included(end) => (self.compare)(cur, end) == Ordering::Greater,
+            Bound::Excluded(end) => (self.compare)(cur, end) != Ordering::Less,
+            Bound::Unbounded => false,
+        };
+        if past_end {
+            return None;
+        }
+
+        self.cursor.move_next();
+        Some(cur)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::{SortedGList, SortedGListBy};
+
+    fn contents<T: Copy, F>(list: &SortedGListBy<T, F>) -> Vec<T>
+    where
+        F: Fn(&T, &T) -> std::cmp::Ordering,
+    {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut list = SortedGList::new();
+        assert!(list.is_empty());
+        assert_eq!(list.first(), None);
+
+        for x in [5, 1, 4, 1, 5, 9, 2, 6] {
+            list.insert(x);
+        }
+        assert_eq!(contents(&list), vec![1, 1, 2, 4, 5, 5, 6, 9]);
+        assert_eq!(list.len(), 8);
+        assert_eq!(list.first(), Some(&1));
+        assert_eq!(list.last(), Some(&9));
+        assert!(list.as_list().is_sorted());
+
+        assert_eq!(list.pop_first(), Some(1));
+        assert_eq!(list.pop_last(), Some(9));
+        assert_eq!(contents(&list), vec![1, 2, 4, 5, 5, 6]);
+
+        assert!(list.contains(&4));
+        assert!(!list.contains(&3));
+        assert_eq!(list.remove(&5), Some(5));
+        assert_eq!(list.remove(&3), None);
+        assert_eq!(list.remove(&100), None);
+        assert_eq!(contents(&list), vec![1, 2, 4, 5, 6]);
+
+        list.clear();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn stable_inserts() {
+        // Deadlines with the same time have to come out in the order they went in
+        let mut list = SortedGListBy::new_by(|a: &(u32, char), b: &(u32, char)| a.0.cmp(&b.0));
+        list.insert((3, 'a'));
+        list.insert((1, 'b'));
+        list.insert((3, 'c'));
+        list.insert((1, 'd'));
+        list.insert((2, 'e'));
+        list.insert((3, 'f'));
+        assert_eq!(
+            contents(&list),
+            vec![(1, 'b'), (1, 'd'), (2, 'e'), (3, 'a'), (3, 'c'), (3, 'f')]
+        );
+
+        // remove() takes the first of the equal ones
+        assert_eq!(list.remove(&(3, 'z')), Some((3, 'a')));
+    }
+
+    #[test]
+    fn ranges() {
+        let list: SortedGList<u32> = (0..10).rev().collect();
+        let range = |r: std::ops::Range<u32>| list.range(r).copied().collect::<Vec<_>>();
+        assert_eq!(range(3..6), vec![3, 4, 5]);
+        assert_eq!(range(8..20), vec![8, 9]);
+        assert_eq!(range(5..5), Vec::<u32>::new());
+        assert_eq!(range(20..30), Vec::<u32>::new());
+
+        assert_eq!(list.range(..3).copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(list.range(7..).copied().collect::<Vec<_>>(), vec![7, 8, 9]);
+        assert_eq!(list.range(2..=4).copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(list.range(..).count(), 10);
+
+        // Doesn't wrap back round once it runs off the end
+        let mut it = list.range(9..);
+        assert_eq!(it.next(), Some(&9));
+        assert_eq!(it.next(), None);
+        assert_eq!(it.next(), None);
+    }
+
+    #[test]
+    fn bulk_build() {
+        let list: SortedGList<i32> = vec![3, -1, 7, 0, 3, -5].into_iter().collect();
+        assert_eq!(contents(&list), vec![-5, -1, 0, 3, 3, 7]);
+
+        // Backwards by comparator
+        let list = SortedGListBy::from_iter_by(0..5, |a: &i32, b: &i32| b.cmp(a));
+        assert_eq!(contents(&list), vec![4, 3, 2, 1, 0]);
+        let mut list = list;
+        list.insert(2);
+        assert_eq!(contents(&list), vec![4, 3, 2, 2, 1, 0]);
+
+        let back: Vec<i32> = list.clone().into_iter().collect();
+        assert_eq!(back, vec![4, 3, 2, 2, 1, 0]);
+        assert_eq!(format!("{:?}", list.into_list()), "[4, 3, 2, 2, 1, 0]");
+    }
+}
@@ -0,0 +1,1139 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Bound, RangeBounds, Sub, SubAssign};
+
+use crate::gangsterlist::{self, Cursor, CursorMut, GList, NodeHandle};
+
+/*
+* TextBuffer is a text editor's buffer, a rope made out of a GList of chunks.
+*
+* Each chunk is a String of at most chunk_size bytes (1 KiB unless you say
+* otherwise), always cut on a char boundary, that remembers how many chars and
+* how many '\n's it holds. So finding the chunk a char, byte or line lives in
+* only adds up those counts, it never looks at the text, and an edit only ever
+* copies text around inside the chunks it touches. Editing the middle of a
+* huge file is O(chunk + log chunks), not O(file).
+*
+* Chunks are kept between a quarter full and full (a buffer with one chunk is
+* allowed to be smaller):
+*
+* insert() puts the text straight into its chunk if it fits. If it doesn't, the
+* chunk and the new text get cut up into as few even-sized chunks as it takes.
+* delete() trims the chunks at either end of the range and drops whole chunks
+* out of the middle. The ends then get mended: a chunk that got too small is
+* merged into its neighbour, or evened out with it if both won't fit in one.
+*
+* Adding up the counts doesn't walk the chunk list either. On top of it sits a
+* skip list, same idea as IndexedGList: chunks flip coins for a tower, towers
+* hold a NodeHandle to their chunk, and every link on every level remembers
+* how many chunks, bytes, chars and lines it jumps over. Going down the levels
+* gets within a couple of chunks of any char, byte, line or chunk index in
+* O(log chunks) expected, and a cursor walks the rest. Every edit fixes up the
+* links over the chunks it touched on the way back, which is O(log chunks) too.
+*
+* Offsets are counted in chars (Unicode scalar values) unless the name says
+* bytes. Lines are split on '\n', so there's always one more line than there
+* are '\n's, and line(n) doesn't include the '\n' itself.
+*/
+
+pub struct TextBuffer {
+    chunks: GList<Chunk>,
+    index: ChunkIndex,
+    total: Pos,
+    chunk_size: usize,
+}
+
+pub struct Chunks<'a> {
+    inner: gangsterlist::Iter<'a, Chunk>,
+}
+
+#[derive(Clone)]
+struct Chunk {
+    text: String,
+    chars: usize,
+    lines: usize,
+}
+
+// A spot in the buffer, counted every way at once. lines is how many '\n's
+// come before it, which is also which line it's on.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+struct Pos {
+    bytes: usize,
+    chars: usize,
+    lines: usize,
+}
+
+// The skip list over the chunks. Ranks count the header as 0, so the chunk
+// at index i has rank i + 1.
+struct ChunkIndex {
+    towers: Vec<Tower>,
+    free: Vec<usize>,
+    height: usize,
+    seed: u64,
+}
+
+struct Tower {
+    // None for the header
+    handle: Option<NodeHandle<Chunk>>,
+    levels: Vec<Level>,
+}
+
+#[derive(Clone, Copy)]
+struct Level {
+    next: Option<usize>,
+    // Everything after this tower's chunk up to the end of next's.
+    // Only means anything when next is Some
+    span: Span,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Span {
+    chunks: usize,
+    pos: Pos,
+}
+
+// Where a search got to on some level: a tower, its rank and where its chunk ends
+#[derive(Clone, Copy)]
+struct Step {
+    tower: usize,
+    rank: usize,
+    end: Pos,
+}
+
+const DEFAULT_CHUNK_SIZE: usize = 1024;
+const MAX_LEVEL: usize = 32;
+const HEADER: usize = 0;
+
+impl TextBuffer {
+
+    pub fn new() -> Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    // chunk_size is in bytes. It has to leave room to cut around multibyte
+    // chars, so anything under 32 panics.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        assert!(chunk_size >= 32, "TextBuffer chunks need to be at least 32 bytes");
+
+        TextBuffer {
+            chunks: GList::new(),
+            index: ChunkIndex::new(),
+            total: Pos::default(),
+            chunk_size,
+        }
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.total.chars
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.total.bytes
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.total.lines + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total.bytes == 0
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    // char_offset == len_chars() appends, past that panics
+    pub fn insert(&mut self, char_offset: usize, text: &str) {
+        assert!(char_offset <= self.total.chars, "Cannot insert past the end of the buffer");
+        if text.is_empty() {
+            return;
+        }
+
+        let added = Pos::of(text);
+        if self.chunks.is_empty() {
+            let mut cursor = self.chunks.cursor_mut();
+            for piece in split_chunks(text, self.chunk_size).into_iter().rev() {
+                insert_chunk_after(&mut cursor, &mut self.index, piece, Pos::default());
+            }
+            self.total = added;
+            return;
+        }
+
+        // Any chunk touching char_offset will do, on a boundary between two
+        // chunks we go with the one in front
+        let (mut cursor, start) = if char_offset == 0 {
+            (self.chunks.cursor_front_mut(), Pos::default())
+        } else {
+            Self::seek_mut(&mut self.chunks, &self.index, |_, end| end.chars < char_offset)
+        };
+        let index = cursor.index().unwrap();
+        let chunk = cursor.current().unwrap();
+        let old = chunk.pos();
+        let at = chunk.byte_of_char(char_offset - start.chars);
+
+        if chunk.text.len() + text.len() <= self.chunk_size {
+            chunk.text.insert_str(at, text);
+            chunk.chars += added.chars;
+            chunk.lines += added.lines;
+            self.index.resize(index, old, old + added);
+        } else {
+            let mut joined = String::with_capacity(chunk.text.len() + text.len());
+            joined.push_str(&chunk.text[..at]);
+            joined.push_str(text);
+            joined.push_str(&chunk.text[at..]);
+
+            let mut pieces = split_chunks(&joined, self.chunk_size).into_iter();
+            *chunk = pieces.next().unwrap();
+            let first = chunk.pos();
+            self.index.resize(index, old, first);
+            // Last one first, so they end up in order behind this one
+            for piece in pieces.rev() {
+                insert_chunk_after(&mut cursor, &mut self.index, piece, start + first);
+            }
+        }
+
+        self.total += added;
+    }
+
+    // Takes out a range of chars. Same rules as GList::drain, a range that
+    // runs past the end panics.
+    pub fn delete<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (lo, hi) = self.char_range(range);
+        if lo == hi {
+            return;
+        }
+
+        // The chunk char lo is in
+        let (mut cursor, start) = Self::seek_mut(&mut self.chunks, &self.index, |_, end| end.chars <= lo);
+        let index = cursor.index().unwrap();
+        let mut local = lo - start.chars;
+        let mut remaining = hi - lo;
+
+        while remaining > 0 {
+            let at = cursor.index().unwrap();
+            let chunk = cursor.current().unwrap();
+            let take = remaining.min(chunk.chars - local);
+
+            if take == chunk.chars {
+                let gone = chunk.pos();
+                self.total -= gone;
+                cursor.remove_current();
+                self.index.remove(at, gone);
+            } else {
+                let from = chunk.byte_of_char(local);
+                let to = chunk.byte_of_char(local + take);
+                let gone = Pos::of(&chunk.text[from..to]);
+                let old = chunk.pos();
+
+                chunk.text.replace_range(from..to, "");
+                chunk.chars -= gone.chars;
+                chunk.lines -= gone.lines;
+                self.total -= gone;
+                self.index.resize(at, old, old - gone);
+                cursor.move_next();
+            }
+
+            remaining -= take;
+            local = 0;
+        }
+
+        self.mend(index);
+    }
+
+    // The whole line, without its '\n'
+    pub fn line(&self, line: usize) -> Option<String> {
+        let start = self.pos_at_line(line)?;
+        let end = match self.pos_at_line(line + 1) {
+            Some(next) => next.bytes - 1,
+            None => self.total.bytes,
+        };
+        Some(self.text_between(start.bytes, end))
+    }
+
+    // Copies out a range of chars
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> String {
+        let (lo, hi) = self.char_range(range);
+        let lo = self.pos_at_char(lo).unwrap();
+        let hi = self.pos_at_char(hi).unwrap();
+        self.text_between(lo.bytes, hi.bytes)
+    }
+
+    // All the conversions take an offset that's at most the length (or a
+    // line that's less than line_count()) and hand back None otherwise
+
+    pub fn char_to_byte(&self, char_offset: usize) -> Option<usize> {
+        self.pos_at_char(char_offset).map(|pos| pos.bytes)
+    }
+
+    // None if byte_offset lands in the middle of a char
+    pub fn byte_to_char(&self, byte_offset: usize) -> Option<usize> {
+        self.pos_at_byte(byte_offset).map(|pos| pos.chars)
+    }
+
+    // Which line the char at char_offset is on
+    pub fn char_to_line(&self, char_offset: usize) -> Option<usize> {
+        self.pos_at_char(char_offset).map(|pos| pos.lines)
+    }
+
+    pub fn byte_to_line(&self, byte_offset: usize) -> Option<usize> {
+        self.pos_at_byte(byte_offset).map(|pos| pos.lines)
+    }
+
+    // Where the line starts
+    pub fn line_to_char(&self, line: usize) -> Option<usize> {
+        self.pos_at_line(line).map(|pos| pos.chars)
+    }
+
+    pub fn line_to_byte(&self, line: usize) -> Option<usize> {
+        self.pos_at_line(line).map(|pos| pos.bytes)
+    }
+
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks {
+            inner: self.chunks.iter(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.index = ChunkIndex::new();
+        self.total = Pos::default();
+    }
+
+    fn char_range<R: RangeBounds<usize>>(&self, range: R) -> (usize, usize) {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1).expect("Range start overflowed"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1).expect("Range end overflowed"),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.total.chars,
+        };
+        assert!(start <= end, "Range starts after it ends");
+        assert!(end <= self.total.chars, "Range ends past the end of the buffer");
+        (start, end)
+    }
+
+    fn pos_at_char(&self, char_offset: usize) -> Option<Pos> {
+        if char_offset > self.total.chars {
+            return None;
+        }
+        if char_offset == 0 {
+            return Some(Pos::default());
+        }
+
+        let (cursor, start) = self.seek(|_, end| end.chars < char_offset);
+        let chunk = cursor.current().unwrap();
+        let local = chunk.byte_of_char(char_offset - start.chars);
+        Some(start + Pos::of(&chunk.text[..local]))
+    }
+
+    fn pos_at_byte(&self, byte_offset: usize) -> Option<Pos> {
+        if byte_offset > self.total.bytes {
+            return None;
+        }
+        if byte_offset == 0 {
+            return Some(Pos::default());
+        }
+
+        let (cursor, start) = self.seek(|_, end| end.bytes < byte_offset);
+        let chunk = cursor.current().unwrap();
+        let local = byte_offset - start.bytes;
+        // Chunks are cut on char boundaries, so checking inside the chunk is enough
+        chunk
+            .text
+            .is_char_boundary(local)
+            .then(|| start + Pos::of(&chunk.text[..local]))
+    }
+
+    // Just past the '\n' that ends the line before
+    fn pos_at_line(&self, line: usize) -> Option<Pos> {
+        if line > self.total.lines {
+            return None;
+        }
+        if line == 0 {
+            return Some(Pos::default());
+        }
+
+        let (cursor, start) = self.seek(|_, end| end.lines < line);
+        let chunk = cursor.current().unwrap();
+        let local = chunk.byte_after_line(line - start.lines);
+        Some(start + Pos::of(&chunk.text[..local]))
+    }
+
+    // Copies out [lo, hi) in bytes, both have to be char boundaries
+    fn text_between(&self, lo: usize, hi: usize) -> String {
+        let mut out = String::with_capacity(hi - lo);
+        if lo == hi {
+            return out;
+        }
+
+        let (mut cursor, start) = self.seek(|_, end| end.bytes <= lo);
+        let mut chunk_start = start.bytes;
+        let mut local = lo - chunk_start;
+
+        loop {
+            let chunk = cursor.current().unwrap();
+            let end = (hi - chunk_start).min(chunk.text.len());
+            out.push_str(&chunk.text[local..end]);
+
+            chunk_start += chunk.text.len();
+            if chunk_start >= hi {
+                return out;
+            }
+            local = 0;
+            cursor.move_next();
+        }
+    }
+
+    // Finds the first chunk past() says no to and where it starts. past gets
+    // each chunk's rank and where it ends, and once it says no it has to keep
+    // saying no. There has to be such a chunk.
+    fn seek(&self, past: impl Fn(usize, Pos) -> bool) -> (Cursor<'_, Chunk>, Pos) {
+        let step = self.index.find(&past)[0];
+        let mut cursor = match self.index.towers[step.tower].handle {
+            Some(handle) => self
+                .chunks
+                .cursor_at(&handle, step.rank - 1)
+                .expect("Chunk index tower lost its chunk"),
+            None => self.chunks.cursor(),
+        };
+
+        // The index got us to the last tower past() says yes to, the chunk
+        // we want is a couple of steps on from there
+        let (mut rank, mut start) = (step.rank, step.end);
+        loop {
+            cursor.move_next();
+            rank += 1;
+            let end = start + cursor.current().unwrap().pos();
+            if !past(rank, end) {
+                return (cursor, start);
+            }
+            start = end;
+        }
+    }
+
+    // Same thing with a mutable cursor. Takes the fields it needs so the
+    // rest of self stays free.
+    fn seek_mut<'a>(
+        chunks: &'a mut GList<Chunk>,
+        index: &ChunkIndex,
+        past: impl Fn(usize, Pos) -> bool,
+    ) -> (CursorMut<'a, Chunk>, Pos) {
+        let step = index.find(&past)[0];
+        let mut cursor = match index.towers[step.tower].handle {
+            Some(handle) => chunks
+                .cursor_mut_at(&handle, step.rank - 1)
+                .expect("Chunk index tower lost its chunk"),
+            None => chunks.cursor_mut(),
+        };
+
+        let (mut rank, mut start) = (step.rank, step.end);
+        loop {
+            cursor.move_next();
+            rank += 1;
+            let end = start + cursor.current().unwrap().pos();
+            if !past(rank, end) {
+                return (cursor, start);
+            }
+            start = end;
+        }
+    }
+
+    // After a delete, the chunk at index and the one after it might have
+    // gotten too small. Merges or evens them out with their neighbours.
+    fn mend(&mut self, index: usize) {
+        if self.chunks.is_empty() {
+            return;
+        }
+
+        // If the delete ran to the end, mend the new last chunk instead
+        let index = index.min(self.chunks.len() - 1);
+        let min = self.chunk_size / 4;
+        let (mut cursor, mut start) = Self::seek_mut(&mut self.chunks, &self.index, |rank, _| rank <= index);
+
+        // Both ends of the delete can be left small, so keep going until
+        // the merged chunk is big enough or there's nothing left to eat
+        loop {
+            let small = cursor.current().unwrap().text.len() < min;
+            match cursor.peek_next() {
+                Some(next) if small || next.text.len() < min => {
+                    join_next(&mut cursor, &mut self.index, self.chunk_size, start);
+                }
+                _ => break,
+            }
+        }
+
+        if cursor.current().unwrap().text.len() < min && cursor.peek_prev().is_some() {
+            cursor.move_prev();
+            start -= cursor.current().unwrap().pos();
+            join_next(&mut cursor, &mut self.index, self.chunk_size, start);
+        }
+    }
+
+    #[cfg(test)]
+    fn check_chunks(&self) {
+        let min = self.chunk_size / 4;
+        let mut total = Pos::default();
+
+        for chunk in self.chunks.iter() {
+            assert_eq!(chunk.pos(), Pos::of(&chunk.text));
+            assert!(!chunk.text.is_empty());
+            assert!(chunk.text.len() <= self.chunk_size);
+            assert!(self.chunks.len() == 1 || chunk.text.len() >= min);
+            total += chunk.pos();
+        }
+        assert_eq!(total, self.total);
+        self.check_index();
+    }
+
+    // Walks every level of the index and checks the spans add up to what's
+    // really in the chunks, and every tower sits on the right chunk
+    #[cfg(test)]
+    fn check_index(&self) {
+        let ends: Vec<Pos> = self
+            .chunks
+            .iter()
+            .scan(Pos::default(), |end, chunk| {
+                *end += chunk.pos();
+                Some(*end)
+            })
+            .collect();
+        let mut live = 0;
+
+        for l in 0..self.index.height {
+            let (mut x, mut rank, mut end) = (HEADER, 0, Pos::default());
+            while let Some(next) = self.index.towers[x].levels[l].next {
+                let span = self.index.towers[x].levels[l].span;
+                rank += span.chunks;
+                end += span.pos;
+                assert!(rank <= self.chunks.len(), "span runs off the end on level {}", l);
+                assert_eq!(end, ends[rank - 1], "span adds up wrong on level {}", l);
+
+                let handle = self.index.towers[next].handle.expect("tower without a chunk");
+                let chunk = self.chunks.get_by_handle(&handle).expect("tower with a stale handle");
+                assert!(std::ptr::eq(chunk, self.chunks.get(rank - 1).unwrap()), "tower in the wrong place on level {}", l);
+
+                if l == 0 {
+                    live += 1;
+                }
+                x = next;
+            }
+        }
+
+        assert_eq!(live, self.index.towers.len() - 1 - self.index.free.len(), "leaked towers");
+    }
+}
+
+impl ChunkIndex {
+    fn new() -> Self {
+        ChunkIndex {
+            towers: vec![Tower {
+                handle: None,
+                levels: vec![Level::EMPTY; MAX_LEVEL],
+            }],
+            free: Vec::new(),
+            height: 0,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    // For every level, the last tower past() says yes to
+    fn find(&self, past: impl Fn(usize, Pos) -> bool) -> [Step; MAX_LEVEL] {
+        let mut steps = [Step::HEADER; MAX_LEVEL];
+        let mut step = Step::HEADER;
+
+        for l in (0..self.height).rev() {
+            while let Some(next) = self.towers[step.tower].levels[l].next {
+                let span = self.towers[step.tower].levels[l].span;
+                let (rank, end) = (step.rank + span.chunks, step.end + span.pos);
+                if !past(rank, end) {
+                    break;
+                }
+                step = Step { tower: next, rank, end };
+            }
+            steps[l] = step;
+        }
+
+        steps
+    }
+
+    // The cursor's chunk was just linked in and starts at start
+    fn insert(&mut self, cursor: &mut CursorMut<'_, Chunk>, start: Pos) {
+        let index = cursor.index().unwrap();
+        let weight = cursor.current().unwrap().pos();
+
+        let height = self.random_height();
+        self.grow(height);
+
+        let steps = self.find(|rank, _| rank <= index);
+        let x = (height > 0).then(|| self.alloc_tower(cursor.current_handle(), height));
+
+        for (l, step) in steps.iter().enumerate().take(self.height) {
+            let level = self.towers[step.tower].levels[l];
+            match x {
+                Some(x) if l < height => {
+                    // Cut step's link in two around the new chunk
+                    self.towers[x].levels[l] = Level {
+                        next: level.next,
+                        span: match level.next {
+                            Some(_) => Span {
+                                chunks: step.rank + level.span.chunks - index,
+                                pos: step.end + level.span.pos - start,
+                            },
+                            None => Span::default(),
+                        },
+                    };
+                    self.towers[step.tower].levels[l] = Level {
+                        next: Some(x),
+                        span: Span {
+                            chunks: index + 1 - step.rank,
+                            pos: start + weight - step.end,
+                        },
+                    };
+                }
+                // Nothing after us on this level, nothing to bump
+                _ if level.next.is_none() => {}
+                _ => {
+                    let span = &mut self.towers[step.tower].levels[l].span;
+                    span.chunks += 1;
+                    span.pos += weight;
+                }
+            }
+        }
+    }
+
+    // The chunk at index weighing weight was just unlinked
+    fn remove(&mut self, index: usize, weight: Pos) {
+        let steps = self.find(|rank, _| rank <= index);
+        let mut target = None;
+
+        for (l, step) in steps.iter().enumerate().take(self.height) {
+            let level = self.towers[step.tower].levels[l];
+            let Some(next) = level.next else {
+                continue;
+            };
+
+            if step.rank + level.span.chunks == index + 1 {
+                // next is the chunk's own tower, skip over it
+                let gone = self.towers[next].levels[l];
+                self.towers[step.tower].levels[l] = Level {
+                    next: gone.next,
+                    span: match gone.next {
+                        Some(_) => Span {
+                            chunks: level.span.chunks + gone.span.chunks - 1,
+                            pos: level.span.pos + gone.span.pos - weight,
+                        },
+                        None => Span::default(),
+                    },
+                };
+                target = Some(next);
+            } else {
+                let span = &mut self.towers[step.tower].levels[l].span;
+                span.chunks -= 1;
+                span.pos -= weight;
+            }
+        }
+
+        if let Some(x) = target {
+            self.free_tower(x);
+        }
+    }
+
+    // The chunk at index went from weighing old to new
+    fn resize(&mut self, index: usize, old: Pos, new: Pos) {
+        let steps = self.find(|rank, _| rank <= index);
+        for (l, step) in steps.iter().enumerate().take(self.height) {
+            let level = &mut self.towers[step.tower].levels[l];
+            if level.next.is_some() {
+                level.span.pos = level.span.pos - old + new;
+            }
+        }
+    }
+
+    // Flip coins until one comes up tails
+    fn random_height(&mut self) -> usize {
+        // xorshift64, plenty random enough for picking tower heights
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed.trailing_ones() as usize).min(MAX_LEVEL)
+    }
+
+    // New levels start out with nothing on them
+    fn grow(&mut self, height: usize) {
+        for l in self.height..height {
+            self.towers[HEADER].levels[l] = Level::EMPTY;
+        }
+        self.height = self.height.max(height);
+    }
+
+    fn alloc_tower(&mut self, handle: Option<NodeHandle<Chunk>>, height: usize) -> usize {
+        let tower = Tower {
+            handle,
+            levels: vec![Level::EMPTY; height],
+        };
+
+        match self.free.pop() {
+            Some(x) => {
+                self.towers[x] = tower;
+                x
+            }
+            None => {
+                self.towers.push(tower);
+                self.towers.len() - 1
+            }
+        }
+    }
+
+    fn free_tower(&mut self, x: usize) {
+        self.towers[x].handle = None;
+        self.towers[x].levels = Vec::new();
+        self.free.push(x);
+    }
+}
+
+impl Level {
+    const EMPTY: Level = Level {
+        next: None,
+        span: Span {
+            chunks: 0,
+            pos: Pos {
+                bytes: 0,
+                chars: 0,
+                lines: 0,
+            },
+        },
+    };
+}
+
+impl Step {
+    const HEADER: Step = Step {
+        tower: HEADER,
+        rank: 0,
+        end: Pos {
+            bytes: 0,
+            chars: 0,
+            lines: 0,
+        },
+    };
+}
+
+// Links chunk in right behind the cursor (or on the front if it's on the
+// ghost) and puts it in the index. end is where the cursor's chunk ends.
+fn insert_chunk_after(cursor: &mut CursorMut<'_, Chunk>, index: &mut ChunkIndex, chunk: Chunk, end: Pos) {
+    cursor.insert_after(chunk);
+    cursor.move_next();
+    index.insert(cursor, end);
+    cursor.move_prev();
+}
+
+// Moves the next chunk's text onto the end of the current one (which starts
+// at start), then cuts the lot back in two if it's too big
+fn join_next(cursor: &mut CursorMut<'_, Chunk>, index: &mut ChunkIndex, chunk_size: usize, start: Pos) {
+    let at = cursor.index().unwrap();
+    cursor.move_next();
+    let next = cursor.remove_current().unwrap();
+    index.remove(at + 1, next.pos());
+    cursor.move_prev();
+
+    let chunk = cursor.current().unwrap();
+    let old = chunk.pos();
+    chunk.text.push_str(&next.text);
+    chunk.chars += next.chars;
+    chunk.lines += next.lines;
+
+    if chunk.text.len() > chunk_size {
+        let mut pieces = split_chunks(&chunk.text, chunk_size).into_iter();
+        *chunk = pieces.next().unwrap();
+        let first = chunk.pos();
+        index.resize(at, old, first);
+        for piece in pieces.rev() {
+            insert_chunk_after(cursor, index, piece, start + first);
+        }
+    } else {
+        let new = chunk.pos();
+        index.resize(at, old, new);
+    }
+}
+
+// Cuts text into as few chunks as it takes, all about the same size.
+// Every cut might have to slide back over up to 3 bytes of a multibyte
+// char, so we aim 4 bytes under the limit.
+fn split_chunks(text: &str, chunk_size: usize) -> Vec<Chunk> {
+    let pieces = text.len().div_ceil(chunk_size - 4).max(1);
+    let mut chunks = Vec::with_capacity(pieces);
+
+    let mut from = 0;
+    for i in 1..=pieces {
+        let mut to = text.len() * i / pieces;
+        while !text.is_char_boundary(to) {
+            to -= 1;
+        }
+        chunks.push(Chunk::new(text[from..to].to_string()));
+        from = to;
+    }
+    chunks
+}
+
+impl Chunk {
+    fn new(text: String) -> Chunk {
+        let pos = Pos::of(&text);
+        Chunk {
+            text,
+            chars: pos.chars,
+            lines: pos.lines,
+        }
+    }
+
+    fn pos(&self) -> Pos {
+        Pos {
+            bytes: self.text.len(),
+            chars: self.chars,
+            lines: self.lines,
+        }
+    }
+
+    // char_offset == chars gives the end
+    fn byte_of_char(&self, char_offset: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_offset)
+            .map_or(self.text.len(), |(byte, _)| byte)
+    }
+
+    // Just past the line-th '\n', counting from 1
+    fn byte_after_line(&self, line: usize) -> usize {
+        self.text.match_indices('\n').nth(line - 1).unwrap().0 + 1
+    }
+}
+
+impl Pos {
+    fn of(text: &str) -> Pos {
+        Pos {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            lines: text.bytes().filter(|&b| b == b'\n').count(),
+        }
+    }
+}
+
+impl Add for Pos {
+    type Output = Pos;
+
+    fn add(self, other: Pos) -> Pos {
+        Pos {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            lines: self.lines + other.lines,
+        }
+    }
+}
+
+impl Sub for Pos {
+    type Output = Pos;
+
+    fn sub(self, other: Pos) -> Pos {
+        Pos {
+            bytes: self.bytes - other.bytes,
+            chars: self.chars - other.chars,
+            lines: self.lines - other.lines,
+        }
+    }
+}
+
+impl AddAssign for Pos {
+    fn add_assign(&mut self, other: Pos) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Pos {
+    fn sub_assign(&mut self, other: Pos) {
+        *self = *self - other;
+    }
+}
+
+impl std::iter::Sum for Pos {
+    fn sum<I: Iterator<Item = Pos>>(iter: I) -> Pos {
+        iter.fold(Pos::default(), Add::add)
+    }
+}
+
+impl Default for TextBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for TextBuffer {
+    fn from(text: &str) -> Self {
+        let mut buffer = TextBuffer::new();
+        buffer.insert(0, text);
+        buffer
+    }
+}
+
+// The index is full of handles into our own list, so the copy builds its own
+impl Clone for TextBuffer {
+    fn clone(&self) -> Self {
+        let mut copy = TextBuffer::with_chunk_size(self.chunk_size);
+        let mut cursor = copy.chunks.cursor_mut();
+        for chunk in self.chunks.iter() {
+            insert_chunk_after(&mut cursor, &mut copy.index, chunk.clone(), copy.total);
+            copy.total += chunk.pos();
+            cursor.move_next();
+        }
+        copy
+    }
+}
+
+impl fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+// Same text means equal, however it happens to be chunked
+impl PartialEq for TextBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.total == other.total && self.chunks().flat_map(str::bytes).eq(other.chunks().flat_map(str::bytes))
+    }
+}
+
+impl Eq for TextBuffer {}
+
+impl PartialEq<str> for TextBuffer {
+    fn eq(&self, other: &str) -> bool {
+        self.total.bytes == other.len() && self.chunks().flat_map(str::bytes).eq(other.bytes())
+    }
+}
+
+impl PartialEq<&str> for TextBuffer {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|chunk| chunk.text.as_str())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Chunks<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|chunk| chunk.text.as_str())
+    }
+}
+
+impl<'a> ExactSizeIterator for Chunks<'a> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::TextBuffer;
+
+    #[test]
+    fn basics() {
+        let mut buffer = TextBuffer::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0), Some(String::new()));
+        assert_eq!(buffer.line(1), None);
+
+        buffer.insert(0, "world");
+        buffer.insert(0, "hello ");
+        buffer.insert(11, "!\nsecond line\n");
+        buffer.check_chunks();
+        assert_eq!(buffer, "hello world!\nsecond line\n");
+        assert_eq!(buffer.len_chars(), 25);
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.line(0).unwrap(), "hello world!");
+        assert_eq!(buffer.line(1).unwrap(), "second line");
+        assert_eq!(buffer.line(2).unwrap(), "");
+
+        buffer.delete(5..11);
+        buffer.delete(..=0);
+        buffer.check_chunks();
+        assert_eq!(buffer, "ello!\nsecond line\n");
+        assert_eq!(buffer.slice(3..12), "o!\nsecond");
+
+        buffer.delete(..);
+        assert!(buffer.is_empty());
+        assert_eq!(format!("{:?}", buffer), r#""""#);
+    }
+
+    #[test]
+    fn multibyte_offsets() {
+        let text = "añb\n日本\n🦀x";
+        let buffer = TextBuffer::from(text);
+        assert_eq!(buffer.len_chars(), text.chars().count());
+        assert_eq!(buffer.len_bytes(), text.len());
+
+        for (chars, (bytes, _)) in text.char_indices().enumerate() {
+            assert_eq!(buffer.char_to_byte(chars), Some(bytes));
+            assert_eq!(buffer.byte_to_char(bytes), Some(chars));
+        }
+        assert_eq!(buffer.char_to_byte(buffer.len_chars()), Some(text.len()));
+        assert_eq!(buffer.char_to_byte(buffer.len_chars() + 1), None);
+        // Middle of the ñ
+        assert_eq!(buffer.byte_to_char(2), None);
+
+        assert_eq!(buffer.char_to_line(3), Some(0));
+        assert_eq!(buffer.char_to_line(4), Some(1));
+        assert_eq!(buffer.byte_to_line(text.len()), Some(2));
+        assert_eq!(buffer.line_to_char(2), Some(7));
+        assert_eq!(buffer.line_to_byte(2), Some(text.find('🦀').unwrap()));
+        assert_eq!(buffer.line_to_char(3), None);
+        assert_eq!(buffer.line(1).unwrap(), "日本");
+    }
+
+    #[test]
+    fn chunks_split_and_mend() {
+        let mut buffer = TextBuffer::with_chunk_size(32);
+        let line = "0123456789abcdef\n";
+        for i in 0..20 {
+            buffer.insert(i * line.len(), line);
+            buffer.check_chunks();
+        }
+        assert!(buffer.chunks().len() > 10);
+        assert!(buffer.chunks().all(|chunk| chunk.len() <= 32));
+        assert_eq!(buffer.line_count(), 21);
+        assert_eq!(buffer.line(13).unwrap(), "0123456789abcdef");
+
+        // Chop bits out all over, chunks have to stay a sensible size
+        let mut model = line.repeat(20);
+        for i in (0..20).rev() {
+            let at = i * line.len();
+            buffer.delete(at + 3..at + 14);
+            model.replace_range(at + 3..at + 14, "");
+            buffer.check_chunks();
+        }
+        assert_eq!(buffer, model.as_str());
+        assert_eq!(buffer.line(0).unwrap(), "012ef");
+        assert_eq!(buffer.line_count(), 21);
+
+        buffer.delete(10..buffer.len_chars() - 10);
+        model.replace_range(10..model.len() - 10, "");
+        buffer.check_chunks();
+        assert_eq!(buffer, model.as_str());
+        // Both halves are still over a quarter full, so they stay apart
+        assert_eq!(buffer.chunks().len(), 2);
+
+        // Trimming the front two chunks down to almost nothing has to pull
+        // in the third too
+        let mut trimmed = TextBuffer::with_chunk_size(32);
+        trimmed.insert(0, &"a".repeat(84));
+        assert_eq!(trimmed.chunks().map(str::len).collect::<Vec<_>>(), vec![28, 28, 28]);
+        trimmed.delete(2..54);
+        trimmed.check_chunks();
+        assert_eq!(trimmed, "a".repeat(32).as_str());
+
+        let copy = buffer.clone();
+        assert_eq!(copy, buffer);
+        assert_eq!(copy, TextBuffer::from(model.as_str()));
+    }
+
+    #[test]
+    fn matches_a_string() {
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let mut random = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+
+        let pieces = ["a", "bc", "\n", "é", "日本語", "🦀🦀", "line\n", "xyzxyzxyzxyzxyzxyzxyz", "\n\n"];
+        let mut buffer = TextBuffer::with_chunk_size(32);
+        let mut model: Vec<char> = Vec::new();
+
+        for _ in 0..600 {
+            if model.is_empty() || random(3) > 0 {
+                let at = random(model.len() + 1);
+                let mut text = String::new();
+                for _ in 0..=random(4) {
+                    text.push_str(pieces[random(pieces.len())]);
+                }
+                buffer.insert(at, &text);
+                model.splice(at..at, text.chars());
+            } else {
+                let lo = random(model.len() + 1);
+                let hi = lo + random(model.len() - lo + 1).min(40);
+                buffer.delete(lo..hi);
+                model.drain(lo..hi);
+            }
+            buffer.check_chunks();
+
+            let text: String = model.iter().collect();
+            assert_eq!(buffer, text.as_str());
+
+            let at = random(model.len() + 1);
+            let bytes = text.char_indices().nth(at).map_or(text.len(), |(byte, _)| byte);
+            assert_eq!(buffer.char_to_byte(at), Some(bytes));
+            assert_eq!(buffer.byte_to_char(bytes), Some(at));
+            assert_eq!(buffer.char_to_line(at), Some(text[..bytes].matches('\n').count()));
+
+            let lines: Vec<&str> = text.split('\n').collect();
+            assert_eq!(buffer.line_count(), lines.len());
+            let n = random(lines.len());
+            assert_eq!(buffer.line(n).unwrap(), lines[n]);
+            assert_eq!(buffer.line_to_char(n), Some(lines[..n].iter().map(|l| l.chars().count() + 1).sum()));
+        }
+    }
+
+    #[test]
+    fn big_file() {
+        // About a megabyte, edited in the middle
+        let line = "The quick brown fox jumps over the lazy dog.\n";
+        let mut buffer = TextBuffer::from(line.repeat(25_000).as_str());
+        assert_eq!(buffer.line_count(), 25_001);
+
+        let middle = buffer.line_to_char(12_500).unwrap();
+        for i in 0..100 {
+            buffer.insert(middle + i, "x");
+        }
+        buffer.delete(middle..middle + 50);
+        buffer.check_chunks();
+
+        assert_eq!(buffer.line(12_500).unwrap(), "x".repeat(50) + line.trim_end());
+        assert_eq!(buffer.line(12_499).unwrap(), line.trim_end());
+        assert_eq!(buffer.len_chars(), line.len() * 25_000 + 50);
+
+        // Jumping all over the place, no two edits anywhere near each other
+        for i in 0..200 {
+            let n = (i * 7_919) % 25_000;
+            let at = buffer.line_to_char(n).unwrap();
+            buffer.insert(at, "y");
+            assert_eq!(buffer.char_to_line(at), Some(n));
+            assert_eq!(buffer.char_to_byte(at), buffer.line_to_byte(n));
+            buffer.delete(at..=at);
+            assert_eq!(buffer.line(n).unwrap().len(), if n == 12_500 { 94 } else { 44 });
+        }
+        buffer.check_chunks();
+        assert_eq!(buffer.len_chars(), line.len() * 25_000 + 50);
+    }
+}
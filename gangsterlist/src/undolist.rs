@@ -0,0 +1,528 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use crate::gangsterlist::{self, GList};
+
+/*
+* UndoGList is a GList that remembers how to take back every change made to it.
+*
+* Every push, pop, insert, remove and replace gets logged as the edit that
+* would undo it (a push_back logs a pop_back, a remove(3) logs an insert at 3
+* of whatever came out, and so on). Running an edit gives back the edit that
+* undoes *that*, so undo() and redo() are the same thing pointed different
+* ways: undo() runs a step off the undo log and files what comes back under
+* redo, redo() does the opposite.
+*
+* Edits pile up in the current step until checkpoint() closes it, and a
+* step gets undone (or redone) all in one go. Call checkpoint() after each
+* thing your user would think of as one action. undo() closes whatever's
+* still open first, so it never leaves half a step behind.
+*
+* Making a new edit throws away the redo log, same as any editor.
+*
+* The history keeps at most history_limit steps, the oldest get dropped
+* (and can't be undone anymore) once there are too many.
+*
+* Anything that hands an element back (pops, remove, replace) also has to
+* keep it for undo, so those need T: Clone.
+*
+* Reads (get, iter, ...) go straight to the list. There's deliberately no
+* get_mut or iter_mut, changes that skip the log would make it lie, use
+* replace() instead.
+*/
+
+pub struct UndoGList<T> {
+    list: GList<T>,
+    // Closed steps, oldest at the front. Each step's edits are in the order
+    // they have to run in.
+    undo: VecDeque<Vec<Edit<T>>>,
+    redo: Vec<Vec<Edit<T>>>,
+    // Undo edits for the step that's still open, in the order they happened
+    pending: Vec<Edit<T>>,
+    history_limit: usize,
+}
+
+#[derive(Clone)]
+enum Edit<T> {
+    PushFront(T),
+    PushBack(T),
+    PopFront,
+    PopBack,
+    Insert(usize, T),
+    Remove(usize),
+    Replace(usize, T),
+}
+
+impl<T> Edit<T> {
+    // Does the edit and hands back the one that reverses it
+    fn apply(self, list: &mut GList<T>) -> Edit<T> {
+        match self {
+            Edit::PushFront(elem) => {
+                list.push_front(elem);
+                Edit::PopFront
+            }
+            Edit::PushBack(elem) => {
+                list.push_back(elem);
+                Edit::PopBack
+            }
+            // The log only ever asks for pops and removes it knows will work
+            Edit::PopFront => Edit::PushFront(list.pop_front().unwrap()),
+            Edit::PopBack => Edit::PushBack(list.pop_back().unwrap()),
+            Edit::Insert(index, elem) => {
+                list.insert(index, elem);
+                Edit::Remove(index)
+            }
+            Edit::Remove(index) => Edit::Insert(index, list.remove(index).unwrap()),
+            Edit::Replace(index, elem) => {
+                let old = mem::replace(list.get_mut(index).unwrap(), elem);
+                Edit::Replace(index, old)
+            }
+        }
+    }
+}
+
+impl<T> UndoGList<T> {
+
+    pub fn new() -> Self {
+        Self::with_history_limit(usize::MAX)
+    }
+
+    pub fn with_history_limit(history_limit: usize) -> Self {
+        UndoGList {
+            list: GList::new(),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            pending: Vec::new(),
+            history_limit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.list.front()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.list.back()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.list.get(index)
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        self.record(Edit::PushFront(elem));
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        self.record(Edit::PushBack(elem));
+    }
+
+    // Popping an empty list doesn't change anything, so it doesn't get logged
+    pub fn pop_front(&mut self) -> Option<T>
+    where
+        T: Clone,
+    {
+        let elem = self.list.pop_front()?;
+        self.log(Edit::PushFront(elem.clone()));
+        Some(elem)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T>
+    where
+        T: Clone,
+    {
+        let elem = self.list.pop_back()?;
+        self.log(Edit::PushBack(elem.clone()));
+        Some(elem)
+    }
+
+    // Same as GList's, index > len panics
+    pub fn insert(&mut self, index: usize, elem: T) {
+        self.record(Edit::Insert(index, elem));
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T>
+    where
+        T: Clone,
+    {
+        let elem = self.list.remove(index)?;
+        self.log(Edit::Insert(index, elem.clone()));
+        Some(elem)
+    }
+
+    // Swaps in a new value, hands back the old one
+    pub fn replace(&mut self, index: usize, elem: T) -> Option<T>
+    where
+        T: Clone,
+    {
+        let old = mem::replace(self.list.get_mut(index)?, elem);
+        self.log(Edit::Replace(index, old.clone()));
+        Some(old)
+    }
+
+    // Closes the open step, everything since the last checkpoint gets
+    // undone together. Does nothing if nothing changed.
+    pub fn checkpoint(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        // Undoing goes newest first
+        let mut step = mem::take(&mut self.pending);
+        step.reverse();
+        self.push_undo(step);
+    }
+
+    // Takes back the last step. Returns false if there's nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.checkpoint();
+        let Some(step) = self.undo.pop_back() else {
+            return false;
+        };
+
+        let redo = self.run(step);
+        self.redo.push(redo);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(step) = self.redo.pop() else {
+            return false;
+        };
+
+        let undo = self.run(step);
+        self.push_undo(undo);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.pending.is_empty() || !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // How many steps undo() can still take back, counting the open one
+    pub fn undo_steps(&self) -> usize {
+        self.undo.len() + usize::from(!self.pending.is_empty())
+    }
+
+    pub fn redo_steps(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.history_limit
+    }
+
+    // Shrinking it drops the oldest steps right away
+    pub fn set_history_limit(&mut self, history_limit: usize) {
+        self.history_limit = history_limit;
+        while self.undo.len() > history_limit {
+            self.undo.pop_front();
+        }
+    }
+
+    // Keeps the list, forgets how it got here
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending.clear();
+    }
+
+    pub fn iter(&self) -> gangsterlist::Iter<'_, T> {
+        self.list.iter()
+    }
+
+    pub fn as_list(&self) -> &GList<T> {
+        &self.list
+    }
+
+    pub fn into_list(self) -> GList<T> {
+        self.list
+    }
+
+    // Applies a brand new edit and logs its undo
+    fn record(&mut self, edit: Edit<T>) {
+        let undo = edit.apply(&mut self.list);
+        self.log(undo);
+    }
+
+    fn log(&mut self, undo: Edit<T>) {
+        self.redo.clear();
+        self.pending.push(undo);
+    }
+
+    // Drops the oldest steps if that makes too many
+    fn push_undo(&mut self, step: Vec<Edit<T>>) {
+        self.undo.push_back(step);
+        while self.undo.len() > self.history_limit {
+            self.undo.pop_front();
+        }
+    }
+
+    // Runs a whole step and hands back the step that reverses it
+    fn run(&mut self, step: Vec<Edit<T>>) -> Vec<Edit<T>> {
+        let mut reverse: Vec<Edit<T>> = step
+            .into_iter()
+            .map(|edit| edit.apply(&mut self.list))
+            .collect();
+        reverse.reverse();
+        reverse
+    }
+}
+
+impl<T> Default for UndoGList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Starts out with no history
+impl<T> From<GList<T>> for UndoGList<T> {
+    fn from(list: GList<T>) -> Self {
+        UndoGList {
+            list,
+            ..UndoGList::new()
+        }
+    }
+}
+
+impl<T> FromIterator<T> for UndoGList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        UndoGList::from(iter.into_iter().collect::<GList<T>>())
+    }
+}
+
+impl<T: Clone> Clone for UndoGList<T> {
+    fn clone(&self) -> Self {
+        UndoGList {
+            list: self.list.clone(),
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+            pending: self.pending.clone(),
+            history_limit: self.history_limit,
+        }
+    }
+}
+
+impl<T: Debug> Debug for UndoGList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.list.fmt(f)
+    }
+}
+
+// Only the contents count, not how they got there
+impl<T: PartialEq> PartialEq for UndoGList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.list == other.list
+    }
+}
+
+impl<T: Eq> Eq for UndoGList<T> {}
+
+impl<T: Hash> Hash for UndoGList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.list.hash(state);
+    }
+}
+
+impl<'a, T> IntoIterator for &'a UndoGList<T> {
+    type IntoIter = gangsterlist::Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::UndoGList;
+    use crate::gangsterlist::GList;
+
+    #[test]
+    fn basics() {
+        let mut list = UndoGList::new();
+        assert!(!list.can_undo());
+        assert!(!list.undo());
+
+        list.push_back(1);
+        list.push_back(2);
+        list.checkpoint();
+        list.push_front(0);
+        list.checkpoint();
+        assert_eq!(format!("{:?}", list), "[0, 1, 2]");
+        assert_eq!(list.undo_steps(), 2);
+
+        assert!(list.undo());
+        assert_eq!(format!("{:?}", list), "[1, 2]");
+        assert!(list.undo());
+        assert!(list.is_empty());
+        assert!(!list.undo());
+
+        assert!(list.redo());
+        assert_eq!(format!("{:?}", list), "[1, 2]");
+        assert!(list.redo());
+        assert!(!list.redo());
+        assert_eq!(format!("{:?}", list), "[0, 1, 2]");
+
+        // Anything new drops what could've been redone
+        list.undo();
+        assert!(list.can_redo());
+        assert_eq!(list.pop_back(), Some(2));
+        assert!(!list.can_redo());
+        assert!(list.undo());
+        assert_eq!(format!("{:?}", list), "[1, 2]");
+    }
+
+    #[test]
+    fn every_kind_of_edit() {
+        let mut list: UndoGList<char> = "abcde".chars().collect();
+        let start = list.clone();
+        assert!(!list.can_undo());
+
+        assert_eq!(list.remove(1), Some('b'));
+        list.checkpoint();
+        list.insert(3, 'x');
+        list.checkpoint();
+        assert_eq!(list.replace(0, 'z'), Some('a'));
+        list.checkpoint();
+        assert_eq!(list.pop_front(), Some('z'));
+        assert_eq!(list.pop_back(), Some('e'));
+        list.checkpoint();
+        // Nothing happened, nothing gets logged
+        assert_eq!(list.remove(10), None);
+        assert_eq!(list.replace(10, 'q'), None);
+        list.checkpoint();
+        assert_eq!(list.iter().collect::<String>(), "cdx");
+        assert_eq!(list.undo_steps(), 4);
+
+        assert!(list.undo());
+        assert_eq!(list.iter().collect::<String>(), "zcdxe");
+        assert!(list.undo());
+        assert_eq!(list.iter().collect::<String>(), "acdxe");
+        assert!(list.undo());
+        assert!(list.undo());
+        assert_eq!(list, start);
+
+        while list.redo() {}
+        assert_eq!(list.iter().collect::<String>(), "cdx");
+    }
+
+    #[test]
+    fn checkpoints_group_edits() {
+        let mut list = UndoGList::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        // Not closed yet, undo takes all three back anyway
+        assert_eq!(list.undo_steps(), 1);
+        assert!(list.undo());
+        assert!(list.is_empty());
+        assert!(list.redo());
+        assert_eq!(list.len(), 3);
+
+        list.checkpoint();
+        list.checkpoint();
+        assert_eq!(list.undo_steps(), 1);
+
+        list.remove(0);
+        list.insert(0, 10);
+        list.replace(2, 30);
+        list.checkpoint();
+        assert_eq!(format!("{:?}", list), "[10, 2, 30]");
+        list.undo();
+        assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+        list.redo();
+        assert_eq!(format!("{:?}", list), "[10, 2, 30]");
+    }
+
+    #[test]
+    fn bounded_history() {
+        let mut list = UndoGList::with_history_limit(3);
+        for i in 0..10 {
+            list.push_back(i);
+            list.checkpoint();
+        }
+        assert_eq!(list.undo_steps(), 3);
+
+        while list.undo() {}
+        assert_eq!(list.len(), 7);
+        assert_eq!(list.redo_steps(), 3);
+
+        list.set_history_limit(1);
+        while list.redo() {}
+        assert_eq!(list.undo_steps(), 1);
+        assert_eq!(list.len(), 10);
+
+        list.clear_history();
+        assert!(!list.can_undo());
+        assert!(!list.can_redo());
+        assert_eq!(list.into_list(), (0..10).collect::<GList<_>>());
+    }
+
+    #[test]
+    fn undo_everything_gets_back_where_we_started() {
+        // Random edits, remembering what the list looked like at every
+        // checkpoint, then undo all the way back and redo all the way forward
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut random = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+
+        let start: GList<usize> = (0..8).collect();
+        let mut list = UndoGList::from(start.clone());
+        let mut states = vec![list.as_list().clone()];
+
+        for step in 0..60 {
+            let steps = list.undo_steps();
+            for _ in 0..=random(3) {
+                let len = list.len();
+                match random(7) {
+                    0 => list.push_front(step),
+                    1 => list.push_back(step),
+                    2 => drop(list.pop_front()),
+                    3 => drop(list.pop_back()),
+                    4 => list.insert(random(len + 1), step),
+                    5 => drop(list.remove(random(len + 1))),
+                    _ => drop(list.replace(random(len + 1), step)),
+                }
+            }
+            list.checkpoint();
+            if list.undo_steps() > steps {
+                states.push(list.as_list().clone());
+            }
+        }
+
+        for state in states.iter().rev().skip(1) {
+            assert!(list.undo());
+            assert_eq!(list.as_list(), state);
+        }
+        assert!(!list.undo());
+        assert_eq!(list.as_list(), &start);
+
+        for state in states.iter().skip(1) {
+            assert!(list.redo());
+            assert_eq!(list.as_list(), state);
+        }
+        assert!(!list.redo());
+    }
+}
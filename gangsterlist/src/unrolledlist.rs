@@ -0,0 +1,832 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::{Index, IndexMut};
+// Actually Wizard Magic!
+use std::ptr;
+use std::ptr::NonNull;
+
+/*
+* UnrolledGList is a GList where every node holds up to N elements in an
+* inline array instead of just one. For small T that's the difference
+* between two pointers plus an allocation per element and two pointers
+* plus an allocation per N of them.
+*
+* Elements in a node are packed at the start of its array, so the front of
+* a node is index 0. push_* fill up the end nodes and start a new node when
+* they're full, pop_* empty them and free a node when it runs dry.
+*
+* insert() into a full node splits it into two half full ones first.
+* remove() leaving a node under half full either merges it with a
+* neighbour (if they'd fit together) or borrows an element from it.
+* That keeps every node at least half full, except the two on the ends,
+* which push and pop are allowed to leave however they like.
+*
+* N has to be at least 2, otherwise there's nothing to unroll.
+*/
+
+pub struct UnrolledGList<T, const N: usize = 16> {
+    front: Link<T, N>,
+    back: Link<T, N>,
+    len: usize,
+    _boo: PhantomData<T>,
+}
+
+type Link<T, const N: usize> = Option<NonNull<Node<T, N>>>;
+
+struct Node<T, const N: usize> {
+    front: Link<T, N>,
+    back: Link<T, N>,
+    // elems[..len] are initialized, the rest aren't
+    len: usize,
+    elems: [MaybeUninit<T>; N],
+}
+
+pub struct Iter<'a, T, const N: usize = 16> {
+    front: Link<T, N>,
+    front_index: usize,
+    back: Link<T, N>,
+    // One past the last element we haven't handed out in back
+    back_end: usize,
+    len: usize,
+    _boo: PhantomData<&'a T>,
+}
+
+pub struct IterMut<'a, T, const N: usize = 16> {
+    front: Link<T, N>,
+    front_index: usize,
+    back: Link<T, N>,
+    back_end: usize,
+    len: usize,
+    _boo: PhantomData<&'a mut T>,
+}
+
+pub struct IntoIter<T, const N: usize = 16> {
+    list: UnrolledGList<T, N>,
+}
+
+impl<T, const N: usize> Node<T, N> {
+
+    fn alloc() -> NonNull<Node<T, N>> {
+        const { assert!(N >= 2, "UnrolledGList needs room for at least 2 elements per node") };
+
+        let node = Box::new(Node {
+            front: None,
+            back: None,
+            len: 0,
+            elems: [const { MaybeUninit::uninit() }; N],
+        });
+        unsafe { NonNull::new_unchecked(Box::into_raw(node)) }
+    }
+
+    fn slot(&mut self, index: usize) -> *mut T {
+        self.elems[index].as_mut_ptr()
+    }
+
+    // Same as slot() but without ever making a &mut Node, for IterMut
+    unsafe fn raw_slot(node: NonNull<Node<T, N>>, index: usize) -> *mut T {
+        ptr::addr_of_mut!((*node.as_ptr()).elems).cast::<T>().add(index)
+    }
+
+    fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.elems.as_ptr().cast::<T>(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.elems.as_mut_ptr().cast::<T>(), self.len) }
+    }
+
+    // Shuffles everything from index on up one. Node can't be full!
+    fn insert(&mut self, index: usize, elem: T) {
+        debug_assert!(self.len < N && index <= self.len);
+
+        unsafe {
+            let slot = self.slot(index);
+            ptr::copy(slot, slot.add(1), self.len - index);
+            slot.write(elem);
+        }
+        self.len += 1;
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        debug_assert!(index < self.len);
+
+        // Shrink first, if anything goes wrong we'd rather leak than double drop
+        self.len -= 1;
+        unsafe {
+            let slot = self.slot(index);
+            let elem = slot.read();
+            ptr::copy(slot.add(1), slot, self.len - index);
+            elem
+        }
+    }
+
+    // Moves elems[at..] into the front of other, which has to be empty
+    fn split_into(&mut self, at: usize, other: &mut Node<T, N>) {
+        debug_assert!(other.len == 0 && at <= self.len);
+
+        let count = self.len - at;
+        self.len = at;
+        unsafe {
+            ptr::copy_nonoverlapping(self.slot(at), other.slot(0), count);
+        }
+        other.len = count;
+    }
+
+    // Moves everything in other onto our end, they have to fit
+    fn append(&mut self, other: &mut Node<T, N>) {
+        debug_assert!(self.len + other.len <= N);
+
+        let count = other.len;
+        other.len = 0;
+        unsafe {
+            ptr::copy_nonoverlapping(other.slot(0), self.slot(self.len), count);
+        }
+        self.len += count;
+    }
+}
+
+impl<T, const N: usize> Drop for Node<T, N> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.as_mut_slice());
+        }
+    }
+}
+
+impl<T> UnrolledGList<T> {
+
+    // The default node size, see Default for any other N
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, const N: usize> UnrolledGList<T, N> {
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        unsafe {
+            let node = match self.front {
+                Some(front) if (*front.as_ptr()).len < N => front,
+                _ => {
+                    let node = Node::alloc();
+                    self.link_between(node, None, self.front);
+                    node
+                }
+            };
+            (*node.as_ptr()).insert(0, elem);
+        }
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        unsafe {
+            let node = match self.back {
+                Some(back) if (*back.as_ptr()).len < N => back,
+                _ => {
+                    let node = Node::alloc();
+                    self.link_between(node, self.back, None);
+                    node
+                }
+            };
+            let end = (*node.as_ptr()).len;
+            (*node.as_ptr()).insert(end, elem);
+        }
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.front.map(|node| unsafe {
+            let elem = (*node.as_ptr()).remove(0);
+            self.len -= 1;
+            if (*node.as_ptr()).len == 0 {
+                self.free_node(node);
+            }
+            elem
+        })
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.back.map(|node| unsafe {
+            let last = (*node.as_ptr()).len - 1;
+            let elem = (*node.as_ptr()).remove(last);
+            self.len -= 1;
+            if (*node.as_ptr()).len == 0 {
+                self.free_node(node);
+            }
+            elem
+        })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        unsafe { self.front.and_then(|node| (*node.as_ptr()).as_slice().first()) }
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.front.and_then(|node| (*node.as_ptr()).as_mut_slice().first_mut()) }
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        unsafe { self.back.and_then(|node| (*node.as_ptr()).as_slice().last()) }
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.back.and_then(|node| (*node.as_ptr()).as_mut_slice().last_mut()) }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            front: self.front,
+            front_index: 0,
+            back: self.back,
+            back_end: unsafe { self.back.map_or(0, |node| (*node.as_ptr()).len) },
+            len: self.len,
+            _boo: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N> {
+        IterMut {
+            front: self.front,
+            front_index: 0,
+            back: self.back,
+            back_end: unsafe { self.back.map_or(0, |node| (*node.as_ptr()).len) },
+            len: self.len,
+            _boo: PhantomData,
+        }
+    }
+
+    // Still a walk, but one node at a time instead of one element
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.locate(index)
+            .map(|(node, offset)| unsafe { &(*node.as_ptr()).as_slice()[offset] })
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.locate(index)
+            .map(|(node, offset)| unsafe { &mut (*node.as_ptr()).as_mut_slice()[offset] })
+    }
+
+    // Like VecDeque, index == len is fine (that's a push_back) but past it panics
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len, "Cannot insert at a nonexistent index");
+
+        if index == self.len {
+            return self.push_back(elem);
+        }
+
+        let (mut node, mut offset) = self.locate(index).unwrap();
+        unsafe {
+            if (*node.as_ptr()).len == N {
+                // Full, split it down the middle and go into whichever half
+                let half = N / 2;
+                let upper = Node::alloc();
+                (*node.as_ptr()).split_into(half, &mut *upper.as_ptr());
+                self.link_between(upper, Some(node), (*node.as_ptr()).back);
+
+                if offset > half {
+                    node = upper;
+                    offset -= half;
+                }
+            }
+            (*node.as_ptr()).insert(offset, elem);
+        }
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let (node, offset) = self.locate(index)?;
+        unsafe {
+            let elem = (*node.as_ptr()).remove(offset);
+            self.len -= 1;
+            self.rebalance(node);
+            Some(elem)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        unsafe {
+            while let Some(node) = self.front {
+                self.free_node(node);
+            }
+        }
+        self.len = 0;
+    }
+
+    // Which node index lives in and where in that node, walking
+    // in from whichever end is closer
+    fn locate(&self, index: usize) -> Option<(NonNull<Node<T, N>>, usize)> {
+        if index >= self.len {
+            return None;
+        }
+
+        unsafe {
+            if index < self.len / 2 {
+                let mut node = self.front?;
+                let mut offset = index;
+                while offset >= (*node.as_ptr()).len {
+                    offset -= (*node.as_ptr()).len;
+                    node = (*node.as_ptr()).back?;
+                }
+                Some((node, offset))
+            } else {
+                // Count how many we are from the back instead
+                let mut node = self.back?;
+                let mut from_back = self.len - 1 - index;
+                while from_back >= (*node.as_ptr()).len {
+                    from_back -= (*node.as_ptr()).len;
+                    node = (*node.as_ptr()).front?;
+                }
+                Some((node, (*node.as_ptr()).len - 1 - from_back))
+            }
+        }
+    }
+
+    // After a remove, frees node if it's empty, and if it's under half full
+    // merges it with a neighbour or steals an element from one
+    unsafe fn rebalance(&mut self, node: NonNull<Node<T, N>>) {
+        let len = (*node.as_ptr()).len;
+        if len == 0 {
+            return self.free_node(node);
+        }
+        if len >= N / 2 {
+            return;
+        }
+
+        let (left, right) = match ((*node.as_ptr()).front, (*node.as_ptr()).back) {
+            (_, Some(next)) => (node, next),
+            (Some(prev), None) => (prev, node),
+            // All alone, nothing to balance against
+            (None, None) => return,
+        };
+
+        let left = &mut *left.as_ptr();
+        let right_ptr = right;
+        let right = &mut *right.as_ptr();
+        if left.len + right.len <= N {
+            left.append(right);
+            self.free_node(right_ptr);
+        } else if left.len < right.len {
+            let elem = right.remove(0);
+            left.insert(left.len, elem);
+        } else {
+            let elem = left.remove(left.len - 1);
+            right.insert(0, elem);
+        }
+    }
+
+    // Hooks node in between prev and next, None meaning an end of the list
+    unsafe fn link_between(&mut self, node: NonNull<Node<T, N>>, prev: Link<T, N>, next: Link<T, N>) {
+        (*node.as_ptr()).front = prev;
+        (*node.as_ptr()).back = next;
+
+        match prev {
+            Some(prev) => (*prev.as_ptr()).back = Some(node),
+            None => self.front = Some(node),
+        }
+        match next {
+            Some(next) => (*next.as_ptr()).front = Some(node),
+            None => self.back = Some(node),
+        }
+    }
+
+    // Unlinks node and frees it, dropping whatever's still in it
+    unsafe fn free_node(&mut self, node: NonNull<Node<T, N>>) {
+        let boxed = Box::from_raw(node.as_ptr());
+
+        match boxed.front {
+            Some(prev) => (*prev.as_ptr()).back = boxed.back,
+            None => self.front = boxed.back,
+        }
+        match boxed.back {
+            Some(next) => (*next.as_ptr()).front = boxed.front,
+            None => self.back = boxed.front,
+        }
+    }
+
+    // Walks the whole thing and checks the links, the counts, and that
+    // nothing but the end nodes is under half full
+    #[cfg(test)]
+    fn check_invariants(&self) {
+        unsafe {
+            let mut count = 0;
+            let mut prev = None;
+            let mut cur = self.front;
+            while let Some(node) = cur {
+                let n = &*node.as_ptr();
+                assert_eq!(n.front, prev, "bad front link");
+                assert!(n.len > 0, "empty node left in the list");
+                if n.front.is_some() && n.back.is_some() {
+                    assert!(n.len >= N / 2, "inner node only {} full", n.len);
+                }
+                count += n.len;
+                prev = cur;
+                cur = n.back;
+            }
+            assert_eq!(self.back, prev, "back isn't the last node");
+            assert_eq!(self.len, count, "len doesn't add up");
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for UnrolledGList<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+unsafe impl<T: Send, const N: usize> Send for UnrolledGList<T, N> {}
+unsafe impl<T: Sync, const N: usize> Sync for UnrolledGList<T, N> {}
+
+unsafe impl<'a, T: Sync, const N: usize> Send for Iter<'a, T, N> {}
+unsafe impl<'a, T: Sync, const N: usize> Sync for Iter<'a, T, N> {}
+
+unsafe impl<'a, T: Send, const N: usize> Send for IterMut<'a, T, N> {}
+unsafe impl<'a, T: Sync, const N: usize> Sync for IterMut<'a, T, N> {}
+
+impl<T, const N: usize> Default for UnrolledGList<T, N> {
+    fn default() -> Self {
+        UnrolledGList {
+            front: None,
+            back: None,
+            len: 0,
+            _boo: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for UnrolledGList<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T, const N: usize> Extend<T> for UnrolledGList<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for UnrolledGList<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = UnrolledGList::default();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug, const N: usize> Debug for UnrolledGList<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for UnrolledGList<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for UnrolledGList<T, N> {}
+
+impl<T: PartialOrd, const N: usize> PartialOrd for UnrolledGList<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord, const N: usize> Ord for UnrolledGList<T, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+// Same as GList's, so the two hash alike for the same elements
+impl<T: Hash, const N: usize> Hash for UnrolledGList<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for item in self {
+            item.hash(state)
+        }
+    }
+}
+
+impl<T, const N: usize> Index<usize> for UnrolledGList<T, N> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("Out of bounds access")
+    }
+}
+
+impl<T, const N: usize> IndexMut<usize> for UnrolledGList<T, N> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("Out of bounds access")
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a UnrolledGList<T, N> {
+    type IntoIter = Iter<'a, T, N>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize> IntoIterator for UnrolledGList<T, N> {
+    type IntoIter = IntoIter<T, N>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.front.map(|node| unsafe {
+            let node = &*node.as_ptr();
+            let elem = &node.as_slice()[self.front_index];
+            self.front_index += 1;
+            if self.front_index == node.len {
+                // Done with this node, on to the next
+                self.front = node.back;
+                self.front_index = 0;
+            }
+            self.len -= 1;
+            elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for Iter<'a, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.map(|node| unsafe {
+            let node = &*node.as_ptr();
+            self.back_end -= 1;
+            let elem = &node.as_slice()[self.back_end];
+            if self.back_end == 0 {
+                self.back = node.front;
+                self.back_end = self.back.map_or(0, |node| (*node.as_ptr()).len);
+            }
+            self.len -= 1;
+            elem
+        })
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for Iter<'a, T, N> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.front.map(|node| unsafe {
+            // Straight to the slot, no slices, so the &muts we've already
+            // handed out from this node don't get trampled
+            let elem = &mut *Node::raw_slot(node, self.front_index);
+            self.front_index += 1;
+            if self.front_index == (*node.as_ptr()).len {
+                self.front = (*node.as_ptr()).back;
+                self.front_index = 0;
+            }
+            self.len -= 1;
+            elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for IterMut<'a, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.map(|node| unsafe {
+            self.back_end -= 1;
+            let elem = &mut *Node::raw_slot(node, self.back_end);
+            if self.back_end == 0 {
+                self.back = (*node.as_ptr()).front;
+                self.back_end = self.back.map_or(0, |node| (*node.as_ptr()).len);
+            }
+            self.len -= 1;
+            elem
+        })
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for IterMut<'a, T, N> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {
+    fn len(&self) -> usize {
+        self.list.len
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::UnrolledGList;
+    use std::rc::Rc;
+
+    crate::gangsterlist::deque_tests!(UnrolledGList);
+
+    // Same little LCG the GList tests use
+    fn pseudo_random(seed: &mut u64) -> usize {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (*seed >> 33) as usize
+    }
+
+    #[test]
+    fn fills_nodes() {
+        let mut list: UnrolledGList<u32, 4> = UnrolledGList::default();
+        for i in 0..10 {
+            list.push_back(i);
+        }
+        for i in 0..10 {
+            list.push_front(100 + i);
+        }
+        list.check_invariants();
+        assert_eq!(list.len(), 20);
+        assert_eq!(list.front(), Some(&109));
+        assert_eq!(list.back(), Some(&9));
+        assert_eq!(list[10], 0);
+        assert_eq!(list.iter().rev().nth(3), Some(&6));
+
+        for _ in 0..20 {
+            assert!(list.pop_back().is_some());
+            list.check_invariants();
+        }
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+    }
+
+    #[test]
+    fn matches_vec() {
+        let mut list: UnrolledGList<u32, 4> = UnrolledGList::default();
+        let mut model = Vec::new();
+        let mut seed = 11;
+
+        for round in 0..3000 {
+            let value = round as u32;
+            match pseudo_random(&mut seed) % 8 {
+                0 => {
+                    list.push_front(value);
+                    model.insert(0, value);
+                }
+                1 => {
+                    list.push_back(value);
+                    model.push(value);
+                }
+                2 => assert_eq!(list.pop_front(), (!model.is_empty()).then(|| model.remove(0))),
+                3 => assert_eq!(list.pop_back(), model.pop()),
+                4 | 5 => {
+                    let index = pseudo_random(&mut seed) % (model.len() + 1);
+                    list.insert(index, value);
+                    model.insert(index, value);
+                }
+                _ => {
+                    if !model.is_empty() {
+                        let index = pseudo_random(&mut seed) % model.len();
+                        assert_eq!(list.remove(index), Some(model.remove(index)));
+                    }
+                }
+            }
+
+            list.check_invariants();
+            assert_eq!(list.len(), model.len());
+        }
+
+        assert!(list.iter().eq(model.iter()));
+        assert!(list.iter().rev().eq(model.iter().rev()));
+        for (i, elem) in model.iter().enumerate() {
+            assert_eq!(list.get(i), Some(elem));
+        }
+        assert_eq!(list.get(model.len()), None);
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let mut list: UnrolledGList<u32, 4> = (0..8).collect();
+        // Two full nodes, inserting in the middle of one splits it
+        list.insert(1, 100);
+        list.check_invariants();
+        list.insert(6, 200);
+        list.check_invariants();
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 100, 1, 2, 3, 4, 200, 5, 6, 7]);
+
+        // And removing from the middle merges them back up
+        while list.len() > 3 {
+            list.remove(1);
+            list.check_invariants();
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 6, 7]);
+    }
+
+    #[test]
+    fn iter_mut_and_traits() {
+        let mut list: UnrolledGList<u32> = (0..50).collect();
+        for elem in list.iter_mut() {
+            *elem *= 2;
+        }
+        let mut it = list.iter_mut();
+        *it.next_back().unwrap() = 1;
+        *it.next().unwrap() = 3;
+        assert_eq!(it.len(), 48);
+
+        assert_eq!(list[0], 3);
+        assert_eq!(list[25], 50);
+        assert_eq!(list[49], 1);
+        list[25] = 7;
+        assert_eq!(list.get(25), Some(&7));
+
+        let copy = list.clone();
+        assert_eq!(copy, list);
+        let mut bigger = copy.clone();
+        bigger.extend([1, 2, 3]);
+        assert!(bigger > copy);
+        assert_eq!(bigger.into_iter().rev().take(3).collect::<Vec<_>>(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn drops_everything() {
+        let tracker = Rc::new(());
+        let mut list: UnrolledGList<Rc<()>, 4> = UnrolledGList::default();
+        for _ in 0..30 {
+            list.push_back(tracker.clone());
+        }
+        for i in (0..30).step_by(3) {
+            list.remove(i / 2);
+        }
+        assert_eq!(Rc::strong_count(&tracker), 21);
+
+        let mut it = list.into_iter();
+        it.next();
+        it.next_back();
+        drop(it);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+}
@@ -0,0 +1,845 @@
+use std::cell::Cell;
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::{PhantomData, PhantomPinned};
+use std::ops::Deref;
+use std::pin::Pin;
+// Actually Wizard Magic!
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+/*
+* An intrusive doubly linked list, same NonNull linking as GList except
+* the links live inside your own struct instead of in a node we allocate.
+* That means no allocation per push, and one value can sit in as many lists
+* as it has Links fields:
+*
+*     struct Conn {
+*         host: String,
+*         idle: Links,
+*         per_host: Links,
+*     }
+*
+*     struct Idle;
+*     struct PerHost;
+*     linked!(Conn, idle, Idle);
+*     linked!(Conn, per_host, PerHost);
+*
+*     let mut idle: IntruList<Pin<Rc<Conn>>, Idle> = IntruList::new();
+*     let mut host: IntruList<Pin<Rc<Conn>>, PerHost> = IntruList::new();
+*
+* The list holds the pointer it was handed (Pin<Box<T>>, Pin<Rc<T>> or
+* Pin<&T>) and hands it back on pop / remove. It has to be pinned, since
+* the neighbours point straight at the Links inside the value.
+*
+* The rules, most of which the types enforce for you:
+*
+* Linked is an unsafe trait, the impl has to point at a real Links field
+* of Self and each Tag at a different one. Use linked!() and it will.
+*
+* A value can only be in one list per Tag at a time. Links remembers which
+* list it's in, pushing a value that's already linked panics, and
+* removing a value through the wrong list gives you None.
+*
+* Values can't go away while linked. Box and Rc are owned by the list, and
+* Pin<&'a T> borrows it for 'a, which outlives the list.
+*
+* Nothing in here is Send or Sync, Links is a bunch of Cells.
+*/
+
+// Every list gets its own id so Links can tell which list it's in, 0 means none
+static NEXT_LIST_ID: AtomicU64 = AtomicU64::new(1);
+
+type Link = Option<NonNull<Links>>;
+
+// Embed one of these in your struct for every list it can be in
+pub struct Links {
+    front: Cell<Link>,
+    back: Cell<Link>,
+    owner: Cell<u64>,
+    // Neighbours point right at us, we can't move
+    _pin: PhantomPinned,
+}
+
+/// Finds the `Links` field for `Tag` inside `Self`. Use `linked!` instead
+/// of implementing this by hand.
+///
+/// # Safety
+///
+/// `links` has to return a pointer to a `Links` field inside `*this` (same
+/// field every time, and a different field for every `Tag`), and
+/// `from_links` has to be its exact inverse. Both keep the provenance of the
+/// pointer they're given, so don't go through a reference.
+pub unsafe trait Linked<Tag = ()>: Sized {
+    /// # Safety
+    ///
+    /// `this` has to point at a live `Self`.
+    unsafe fn links(this: NonNull<Self>) -> NonNull<Links>;
+
+    /// # Safety
+    ///
+    /// `links` has to have come from `Self::links`.
+    unsafe fn from_links(links: NonNull<Links>) -> NonNull<Self>;
+}
+
+/// A pinned pointer the list can hold on to as a raw pointer and give back.
+///
+/// # Safety
+///
+/// `into_raw` has to hand out a pointer that stays valid (and doesn't move)
+/// until it's passed back to `from_raw`, which has to rebuild the very same
+/// pointer. Only shared access goes through the raw pointer.
+pub unsafe trait PinnedPtr: Deref {
+    fn into_raw(this: Self) -> NonNull<Self::Target>;
+
+    /// # Safety
+    ///
+    /// `ptr` has to have come from `into_raw`, and only once.
+    unsafe fn from_raw(ptr: NonNull<Self::Target>) -> Self;
+}
+
+pub struct IntruList<P, Tag = ()>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    front: Link,
+    back: Link,
+    len: usize,
+    id: u64,
+    // We own the Ps, and Tag is just a name
+    _boo: PhantomData<(P, fn() -> Tag)>,
+}
+
+pub struct Iter<'a, T, Tag = ()> {
+    front: Link,
+    back: Link,
+    len: usize,
+    _boo: PhantomData<&'a T>,
+    _tag: PhantomData<fn() -> Tag>,
+}
+
+pub struct IntoIter<P, Tag = ()>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    list: IntruList<P, Tag>,
+}
+
+pub struct CursorMut<'a, P, Tag = ()>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    list: &'a mut IntruList<P, Tag>,
+    cur: Link,
+    index: Option<usize>,
+}
+
+/// Expands to the `Linked` impl for a `Links` field.
+///
+/// `linked!(Conn, idle)` links through `Conn::idle` with the default `()` tag,
+/// `linked!(Conn, idle, Idle)` uses `Idle` as the tag.
+#[macro_export]
+macro_rules! linked {
+    ($ty:ty, $field:ident) => {
+        $crate::linked!($ty, $field, ());
+    };
+    ($ty:ty, $field:ident, $tag:ty) => {
+        unsafe impl $crate::intrulist::Linked<$tag> for $ty {
+            unsafe fn links(
+                this: ::std::ptr::NonNull<Self>,
+            ) -> ::std::ptr::NonNull<$crate::intrulist::Links> {
+                ::std::ptr::NonNull::new_unchecked(::std::ptr::addr_of_mut!(
+                    (*this.as_ptr()).$field
+                ))
+            }
+
+            unsafe fn from_links(
+                links: ::std::ptr::NonNull<$crate::intrulist::Links>,
+            ) -> ::std::ptr::NonNull<Self> {
+                let offset = ::std::mem::offset_of!($ty, $field);
+                ::std::ptr::NonNull::new_unchecked(links.as_ptr().byte_sub(offset).cast::<Self>())
+            }
+        }
+    };
+}
+
+impl Links {
+
+    pub const fn new() -> Self {
+        Links {
+            front: Cell::new(None),
+            back: Cell::new(None),
+            owner: Cell::new(0),
+            _pin: PhantomPinned,
+        }
+    }
+
+    // In some list with this field's tag
+    pub fn is_linked(&self) -> bool {
+        self.owner.get() != 0
+    }
+}
+
+impl Default for Links {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for Links {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Links")
+            .field("linked", &self.is_linked())
+            .finish()
+    }
+}
+
+unsafe impl<T> PinnedPtr for Pin<Box<T>> {
+    fn into_raw(this: Self) -> NonNull<T> {
+        unsafe {
+            NonNull::new_unchecked(Box::into_raw(Pin::into_inner_unchecked(this)))
+        }
+    }
+
+    unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        Box::into_pin(Box::from_raw(ptr.as_ptr()))
+    }
+}
+
+unsafe impl<T> PinnedPtr for Pin<Rc<T>> {
+    fn into_raw(this: Self) -> NonNull<T> {
+        unsafe {
+            NonNull::new_unchecked(Rc::into_raw(Pin::into_inner_unchecked(this)) as *mut T)
+        }
+    }
+
+    unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        Pin::new_unchecked(Rc::from_raw(ptr.as_ptr()))
+    }
+}
+
+unsafe impl<T> PinnedPtr for Pin<&T> {
+    fn into_raw(this: Self) -> NonNull<T> {
+        NonNull::from(Pin::get_ref(this))
+    }
+
+    unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        Pin::new_unchecked(&*ptr.as_ptr())
+    }
+}
+
+impl<P, Tag> IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+
+    pub fn new() -> Self {
+        IntruList {
+            front: None,
+            back: None,
+            len: 0,
+            id: NEXT_LIST_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            _boo: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Panics if elem is already in a list with this tag
+    pub fn push_front(&mut self, elem: P) {
+        let node = Self::adopt(elem);
+        unsafe { self.link_between(node, None, self.front) }
+    }
+
+    pub fn push_back(&mut self, elem: P) {
+        let node = Self::adopt(elem);
+        unsafe { self.link_between(node, self.back, None) }
+    }
+
+    pub fn pop_front(&mut self) -> Option<P> {
+        unsafe { self.front.map(|node| self.unlink(node)) }
+    }
+
+    pub fn pop_back(&mut self) -> Option<P> {
+        unsafe { self.back.map(|node| self.unlink(node)) }
+    }
+
+    pub fn front(&self) -> Option<&P::Target> {
+        unsafe { self.front.map(|node| Self::elem(node)) }
+    }
+
+    pub fn back(&self) -> Option<&P::Target> {
+        unsafe { self.back.map(|node| Self::elem(node)) }
+    }
+
+    // Is elem in this list (not just any list)?
+    pub fn contains(&self, elem: &P::Target) -> bool {
+        unsafe { Self::links_of(elem).owner.get() == self.id }
+    }
+
+    // Takes elem out of wherever it is in the list, O(1).
+    // None if it's not in this list.
+    pub fn remove(&mut self, elem: &P::Target) -> Option<P> {
+        if !self.contains(elem) {
+            return None;
+        }
+
+        unsafe {
+            // Go through a neighbour so we rebuild P from the pointer we were
+            // handed in push, not one we made from a shared borrow
+            let node = match Self::links_of(elem).front.get() {
+                Some(prev) => prev.as_ref().back.get(),
+                None => self.front,
+            };
+            node.map(|node| self.unlink(node))
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    pub fn iter(&self) -> Iter<'_, P::Target, Tag> {
+        Iter {
+            front: self.front,
+            back: self.back,
+            len: self.len,
+            _boo: PhantomData,
+            _tag: PhantomData,
+        }
+    }
+
+    // Starts on the ghost, so the first move_next() lands on the front
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, P, Tag> {
+        CursorMut {
+            list: self,
+            cur: None,
+            index: None,
+        }
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, P, Tag> {
+        CursorMut {
+            cur: self.front,
+            index: self.front.map(|_| 0),
+            list: self,
+        }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, P, Tag> {
+        CursorMut {
+            cur: self.back,
+            index: self.back.map(|_| self.len - 1),
+            list: self,
+        }
+    }
+
+    // Checks elem isn't linked anywhere else before we take it over, so a
+    // panic here just drops elem like normal
+    fn adopt(elem: P) -> NonNull<Links> {
+        unsafe {
+            assert!(
+                !Self::links_of(&elem).is_linked(),
+                "Element is already in a list with this tag"
+            );
+            <P::Target as Linked<Tag>>::links(P::into_raw(elem))
+        }
+    }
+
+    unsafe fn links_of(elem: &P::Target) -> &Links {
+        <P::Target as Linked<Tag>>::links(NonNull::from(elem)).as_ref()
+    }
+
+    unsafe fn elem<'a>(node: NonNull<Links>) -> &'a P::Target {
+        <P::Target as Linked<Tag>>::from_links(node).as_ref()
+    }
+
+    // Hooks node in between prev and next, None meaning an end of the list
+    unsafe fn link_between(&mut self, node: NonNull<Links>, prev: Link, next: Link) {
+        let links = node.as_ref();
+        links.owner.set(self.id);
+        links.front.set(prev);
+        links.back.set(next);
+
+        match prev {
+            Some(prev) => prev.as_ref().back.set(Some(node)),
+            None => self.front = Some(node),
+        }
+        match next {
+            Some(next) => next.as_ref().front.set(Some(node)),
+            None => self.back = Some(node),
+        }
+        self.len += 1;
+    }
+
+    // node has to actually be in this list!
+    unsafe fn unlink(&mut self, node: NonNull<Links>) -> P {
+        let links = node.as_ref();
+        let prev = links.front.replace(None);
+        let next = links.back.replace(None);
+        links.owner.set(0);
+
+        match prev {
+            Some(prev) => prev.as_ref().back.set(next),
+            None => self.front = next,
+        }
+        match next {
+            Some(next) => next.as_ref().front.set(prev),
+            None => self.back = prev,
+        }
+        self.len -= 1;
+
+        P::from_raw(<P::Target as Linked<Tag>>::from_links(node))
+    }
+}
+
+impl<P, Tag> Drop for IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    fn drop(&mut self) {
+        // Unlinks everything, so Pin<&T>s are free to join other lists after
+        self.clear();
+    }
+}
+
+impl<P, Tag> Default for IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, Tag> Extend<P> for IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    fn extend<I: IntoIterator<Item = P>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<P, Tag> FromIterator<P> for IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let mut list = IntruList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<P, Tag> Debug for IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag> + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, P, Tag> IntoIterator for &'a IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    type IntoIter = Iter<'a, P::Target, Tag>;
+    type Item = &'a P::Target;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<P, Tag> IntoIterator for IntruList<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    type IntoIter = IntoIter<P, Tag>;
+    type Item = P;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T: Linked<Tag> + 'a, Tag> Iterator for Iter<'a, T, Tag> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.front.map(|node| unsafe {
+            self.len -= 1;
+            self.front = node.as_ref().back.get();
+            T::from_links(node).as_ref()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T: Linked<Tag> + 'a, Tag> DoubleEndedIterator for Iter<'a, T, Tag> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.map(|node| unsafe {
+            self.len -= 1;
+            self.back = node.as_ref().front.get();
+            T::from_links(node).as_ref()
+        })
+    }
+}
+
+impl<'a, T: Linked<Tag> + 'a, Tag> ExactSizeIterator for Iter<'a, T, Tag> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<P, Tag> Iterator for IntoIter<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    type Item = P;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<P, Tag> DoubleEndedIterator for IntoIter<P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<'a, P, Tag> CursorMut<'a, P, Tag>
+where
+    P: PinnedPtr,
+    P::Target: Linked<Tag>,
+{
+
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = cur.as_ref().back.get();
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() += 1;
+                } else {
+                    // Walked off the back and onto the ghost
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.front;
+            self.index = Some(0);
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = cur.as_ref().front.get();
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() -= 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.back;
+            self.index = Some(self.list.len - 1);
+        }
+    }
+
+    // Only ever shared, the values can be in other lists too
+    pub fn current(&self) -> Option<&P::Target> {
+        unsafe { self.cur.map(|node| IntruList::<P, Tag>::elem(node)) }
+    }
+
+    pub fn peek_next(&self) -> Option<&P::Target> {
+        unsafe {
+            let next = match self.cur {
+                Some(cur) => cur.as_ref().back.get(),
+                None => self.list.front,
+            };
+            next.map(|node| IntruList::<P, Tag>::elem(node))
+        }
+    }
+
+    pub fn peek_prev(&self) -> Option<&P::Target> {
+        unsafe {
+            let prev = match self.cur {
+                Some(cur) => cur.as_ref().front.get(),
+                None => self.list.back,
+            };
+            prev.map(|node| IntruList::<P, Tag>::elem(node))
+        }
+    }
+
+    // On the ghost this is a push_back
+    pub fn insert_before(&mut self, elem: P) {
+        let node = IntruList::<P, Tag>::adopt(elem);
+        unsafe {
+            let prev = match self.cur {
+                Some(cur) => cur.as_ref().front.get(),
+                None => self.list.back,
+            };
+            self.list.link_between(node, prev, self.cur);
+        }
+        if let Some(index) = self.index.as_mut() {
+            *index += 1;
+        }
+    }
+
+    // On the ghost this is a push_front
+    pub fn insert_after(&mut self, elem: P) {
+        let node = IntruList::<P, Tag>::adopt(elem);
+        unsafe {
+            let next = match self.cur {
+                Some(cur) => cur.as_ref().back.get(),
+                None => self.list.front,
+            };
+            self.list.link_between(node, self.cur, next);
+        }
+    }
+
+    // Unlinks the current element and moves onto the one after it
+    pub fn remove_current(&mut self) -> Option<P> {
+        let node = self.cur?;
+
+        unsafe {
+            self.cur = node.as_ref().back.get();
+            if self.cur.is_none() {
+                self.index = None;
+            }
+            Some(self.list.unlink(node))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    use super::{IntruList, Links};
+    use std::cell::Cell;
+    use std::pin::{pin, Pin};
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Conn {
+        id: u32,
+        host: &'static str,
+        idle: Links,
+        per_host: Links,
+    }
+
+    struct Idle;
+    struct PerHost;
+    crate::linked!(Conn, idle, Idle);
+    crate::linked!(Conn, per_host, PerHost);
+
+    fn conn(id: u32, host: &'static str) -> Pin<Rc<Conn>> {
+        Rc::pin(Conn {
+            id,
+            host,
+            ..Conn::default()
+        })
+    }
+
+    fn ids<'a>(iter: impl Iterator<Item = &'a Conn>) -> Vec<u32> {
+        iter.map(|conn| conn.id).collect()
+    }
+
+    struct Counted<'a> {
+        value: u32,
+        drops: &'a Cell<u32>,
+        links: Links,
+    }
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    crate::linked!(Counted<'_>, links);
+
+    fn counted(value: u32, drops: &Cell<u32>) -> Pin<Box<Counted<'_>>> {
+        Box::pin(Counted {
+            value,
+            drops,
+            links: Links::new(),
+        })
+    }
+
+    #[test]
+    fn two_lists_at_once() {
+        let mut idle: IntruList<Pin<Rc<Conn>>, Idle> = IntruList::new();
+        let mut example: IntruList<Pin<Rc<Conn>>, PerHost> = IntruList::new();
+
+        for (id, host) in [(1, "example.com"), (2, "other.org"), (3, "example.com")] {
+            let conn = conn(id, host);
+            if host == "example.com" {
+                example.push_back(conn.clone());
+            }
+            idle.push_back(conn);
+        }
+        assert_eq!(ids(idle.iter()), vec![1, 2, 3]);
+        assert_eq!(ids(example.iter()), vec![1, 3]);
+
+        // Grab the oldest idle connection and take it out of its host list too
+        let taken = idle.pop_front().unwrap();
+        assert!(example.contains(&taken));
+        let also = example.remove(&taken).unwrap();
+        assert!(std::ptr::eq(&*taken, &*also));
+        assert_eq!(ids(example.iter()), vec![3]);
+        assert!(!taken.idle.is_linked());
+        assert!(!taken.per_host.is_linked());
+
+        // Removing from a list it isn't in does nothing
+        assert!(example.remove(&taken).is_none());
+        let other = idle.front().unwrap();
+        assert_eq!(other.host, "other.org");
+        assert!(!example.contains(other));
+
+        // Dropping a list unlinks whatever's still in it
+        let three = idle.pop_back().unwrap();
+        assert!(three.per_host.is_linked());
+        drop(example);
+        assert!(!three.per_host.is_linked());
+        assert_eq!(idle.len(), 1);
+    }
+
+    #[test]
+    fn boxed_and_cursor() {
+        let drops = Cell::new(0);
+        let mut list: IntruList<Pin<Box<Counted>>> = (0..5).map(|i| counted(i, &drops)).collect();
+        let values = |list: &IntruList<Pin<Box<Counted>>>| list.iter().map(|c| c.value).collect::<Vec<_>>();
+        assert_eq!(values(&list), vec![0, 1, 2, 3, 4]);
+        assert_eq!(list.iter().rev().map(|c| c.value).collect::<Vec<_>>(), vec![4, 3, 2, 1, 0]);
+
+        let mut cursor = list.cursor_mut();
+        assert!(cursor.current().is_none());
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(cursor.current().map(|c| c.value), Some(1));
+        assert_eq!(cursor.peek_prev().map(|c| c.value), Some(0));
+        assert_eq!(cursor.peek_next().map(|c| c.value), Some(2));
+
+        cursor.insert_before(counted(10, &drops));
+        cursor.insert_after(counted(11, &drops));
+        assert_eq!(cursor.index(), Some(2));
+        let removed = cursor.remove_current().unwrap();
+        assert_eq!(removed.value, 1);
+        assert_eq!(cursor.current().map(|c| c.value), Some(11));
+        drop(removed);
+        assert_eq!(drops.get(), 1);
+
+        // Off the back onto the ghost, then insert_before there is a push_back
+        let mut cursor = list.cursor_back_mut();
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        cursor.insert_before(counted(12, &drops));
+        cursor.insert_after(counted(13, &drops));
+        assert_eq!(values(&list), vec![13, 0, 10, 11, 2, 3, 4, 12]);
+
+        assert_eq!(list.pop_front().map(|c| c.value), Some(13));
+        assert_eq!(list.pop_back().map(|c| c.value), Some(12));
+        assert_eq!(drops.get(), 3);
+
+        let back: Vec<u32> = list.into_iter().rev().map(|c| c.value).collect();
+        assert_eq!(back, vec![4, 3, 2, 11, 10, 0]);
+        assert_eq!(drops.get(), 9);
+    }
+
+    #[test]
+    fn drop_frees_everything() {
+        let drops = Cell::new(0);
+        let mut list = IntruList::new();
+        for i in 0..10 {
+            list.push_front(counted(i, &drops));
+        }
+        assert_eq!(list.len(), 10);
+        drop(list);
+        assert_eq!(drops.get(), 10);
+    }
+
+    #[test]
+    fn borrowed_elements() {
+        let a = pin!(Conn { id: 1, ..Conn::default() });
+        let b = pin!(Conn { id: 2, ..Conn::default() });
+        let (a, b) = (a.into_ref(), b.into_ref());
+
+        {
+            let mut idle: IntruList<Pin<&Conn>, Idle> = IntruList::new();
+            idle.push_back(a);
+            idle.push_front(b);
+            assert_eq!(ids(idle.iter()), vec![2, 1]);
+            assert!(a.idle.is_linked());
+            assert_eq!(format!("{:?}", a.idle), "Links { linked: true }");
+        }
+
+        // The list is gone, so they're free to go in another one
+        assert!(!a.idle.is_linked());
+        let mut idle: IntruList<Pin<&Conn>, Idle> = IntruList::new();
+        idle.push_back(a);
+        idle.push_back(b);
+        assert!(idle.remove(&a).is_some());
+        assert_eq!(ids(idle.iter()), vec![2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already in a list")]
+    fn double_push() {
+        let conn = conn(1, "example.com");
+        let mut one: IntruList<Pin<Rc<Conn>>, Idle> = IntruList::new();
+        let mut two: IntruList<Pin<Rc<Conn>>, Idle> = IntruList::new();
+        one.push_back(conn.clone());
+        two.push_back(conn);
+    }
+}
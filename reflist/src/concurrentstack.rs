@@ -0,0 +1,374 @@
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/*
+*
+* ConcurrentStack is a lock-free stack (a Treiber stack) that any number of
+* threads can push onto and pop off of at once through a shared reference.
+*
+* It's the same singly linked shape as ThreadList, except the head is an
+* AtomicPtr and nodes are owned by the stack instead of shared through Arc.
+*
+* push() builds the node, points it at the current head and CAS-es itself in.
+* pop() reads the head, and CAS-es the head over to head.next.
+*
+* The tricky bit is freeing popped nodes, since another pop might still be
+* looking at one (it read the head just before we swung it away). We use
+* hazard pointers for that:
+*
+* Before touching a node a pop() publishes it in a hazard slot, then checks
+* the head is still that node. A popped node isn't freed right away, it goes
+* on the retired list, and once that gets long enough we scan every
+* hazard slot and free whatever nobody has published. Nodes somebody is still
+* looking at stay retired until the next scan.
+*
+* This also keeps ABA away, a node can't be freed and reallocated as a new
+* head while some pop() still has it published.
+*
+* Hazard slots belong to the stack (no thread locals), pop() grabs a free one
+* for the duration and gives it back. There are never more slots than the most
+* pops that were ever running at once.
+*/
+
+pub struct ConcurrentStack<T> {
+    head: AtomicPtr<Node<T>>,
+    // Append only, freed when the stack is
+    hazards: AtomicPtr<HazardSlot>,
+    slots: AtomicUsize,
+    // Popped nodes waiting to be freed, chained through retired_next
+    retired: AtomicPtr<Node<T>>,
+    retired_count: AtomicUsize,
+}
+
+struct Node<T> {
+    // Moved out by whoever wins the pop, the node itself gets freed later
+    elem: ManuallyDrop<T>,
+    // Set before the node is published and never touched again
+    next: *mut Node<T>,
+    // Only the retiring thread and the scanning thread ever touch this
+    retired_next: *mut Node<T>,
+}
+
+struct HazardSlot {
+    protected: AtomicPtr<()>,
+    in_use: AtomicBool,
+    next: *mut HazardSlot,
+}
+
+// How many retired nodes we let pile up before we go scanning
+const RETIRE_THRESHOLD: usize = 64;
+
+impl<T> ConcurrentStack<T> {
+
+    pub fn new() -> Self {
+        ConcurrentStack {
+            head: AtomicPtr::new(ptr::null_mut()),
+            hazards: AtomicPtr::new(ptr::null_mut()),
+            slots: AtomicUsize::new(0),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            retired_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn push(&self, elem: T) {
+        let node = Box::into_raw(Box::new(Node {
+            elem: ManuallyDrop::new(elem),
+            next: ptr::null_mut(),
+            retired_next: ptr::null_mut(),
+        }));
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // Nobody else can see node yet, so this is fine
+            unsafe { (*node).next = head };
+            match self.head.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let slot = self.acquire_slot();
+
+        let popped = loop {
+            let head = self.head.load(Ordering::Acquire);
+            if head.is_null() {
+                break None;
+            }
+
+            // Publish it, then make sure it's still the head. If it is, it
+            // can't have been freed, and can't be until we unpublish it.
+            slot.protected.store(head.cast(), Ordering::SeqCst);
+            if self.head.load(Ordering::SeqCst) != head {
+                continue;
+            }
+
+            let next = unsafe { (*head).next };
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                break Some(head);
+            }
+        };
+
+        slot.protected.store(ptr::null_mut(), Ordering::Release);
+        slot.in_use.store(false, Ordering::Release);
+
+        popped.map(|node| unsafe {
+            // We won the CAS, so the elem is ours and ours alone
+            let elem = ManuallyDrop::take(&mut (*node).elem);
+            self.retire(node);
+            elem
+        })
+    }
+
+    // Just a peek at the head, someone might push or pop right after
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    // Finds a free hazard slot, or hangs a new one on the list
+    fn acquire_slot(&self) -> &HazardSlot {
+        let mut cur = self.hazards.load(Ordering::Acquire);
+        while !cur.is_null() {
+            let slot = unsafe { &*cur };
+            if slot
+                .in_use
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return slot;
+            }
+            cur = slot.next;
+        }
+
+        let slot = Box::into_raw(Box::new(HazardSlot {
+            protected: AtomicPtr::new(ptr::null_mut()),
+            in_use: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.hazards.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*slot).next = head };
+            match self.hazards.compare_exchange_weak(head, slot, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        self.slots.fetch_add(1, Ordering::Relaxed);
+        unsafe { &*slot }
+    }
+
+    // node has to be unlinked already, with its elem moved out
+    unsafe fn retire(&self, node: *mut Node<T>) {
+        // Count it before anyone can find it, or a scan could free it and
+        // subtract it before we ever added it
+        let retired = self.retired_count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.push_retired(node);
+
+        if retired >= RETIRE_THRESHOLD.max(2 * self.slots.load(Ordering::Relaxed)) {
+            self.scan();
+        }
+    }
+
+    unsafe fn push_retired(&self, node: *mut Node<T>) {
+        // Only pushes and take-everything swaps on this one, so no ABA
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            (*node).retired_next = head;
+            match self.retired.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    // Takes the whole retired list, frees everything nobody has published,
+    // and puts the rest back for next time
+    unsafe fn scan(&self) {
+        let mut node = self.retired.swap(ptr::null_mut(), Ordering::Acquire);
+        if node.is_null() {
+            return;
+        }
+
+        // Pairs with pop()'s publish-then-recheck. Either it sees the node
+        // unlinked and backs off, or we see it published and leave it alone.
+        fence(Ordering::SeqCst);
+
+        let mut protected = Vec::new();
+        let mut slot = self.hazards.load(Ordering::Acquire);
+        while !slot.is_null() {
+            let ptr = (*slot).protected.load(Ordering::SeqCst);
+            if !ptr.is_null() {
+                protected.push(ptr.cast::<Node<T>>());
+            }
+            slot = (*slot).next;
+        }
+
+        let mut freed = 0;
+        while !node.is_null() {
+            let next = (*node).retired_next;
+            if protected.contains(&node) {
+                self.push_retired(node);
+            } else {
+                // elem is long gone, and ManuallyDrop keeps Box from dropping it again
+                drop(Box::from_raw(node));
+                freed += 1;
+            }
+            node = next;
+        }
+        self.retired_count.fetch_sub(freed, Ordering::Relaxed);
+    }
+}
+
+impl<T> Default for ConcurrentStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ConcurrentStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Can't look at anything without racing, so don't
+        f.debug_struct("ConcurrentStack").finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for ConcurrentStack<T> {
+    fn drop(&mut self) {
+        // &mut self, so nobody else is in here anymore
+        while self.pop().is_some() {}
+
+        unsafe {
+            let mut node = *self.retired.get_mut();
+            while !node.is_null() {
+                let next = (*node).retired_next;
+                drop(Box::from_raw(node));
+                node = next;
+            }
+
+            let mut slot = *self.hazards.get_mut();
+            while !slot.is_null() {
+                let next = (*slot).next;
+                drop(Box::from_raw(slot));
+                slot = next;
+            }
+        }
+    }
+}
+
+// Elements only ever move in and out whole, nobody gets a &T,
+// so T: Send is all we need to share the stack
+unsafe impl<T: Send> Send for ConcurrentStack<T> {}
+unsafe impl<T: Send> Sync for ConcurrentStack<T> {}
+
+#[cfg(test)]
+mod test {
+
+    use super::ConcurrentStack;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let stack = ConcurrentStack::new();
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
+
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+
+        stack.push(4);
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn every_value_popped_once() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 20_000;
+
+        let stack = ConcurrentStack::new();
+        let mut popped: Vec<usize> = thread::scope(|s| {
+            let workers: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    s.spawn(move || {
+                        let mut mine = Vec::new();
+                        for i in 0..PER_THREAD {
+                            stack.push(t * PER_THREAD + i);
+                            // Pop about every other push, so the stack
+                            // grows and shrinks while everyone's at it
+                            if i % 2 == 1 {
+                                mine.extend(stack.pop());
+                            }
+                        }
+                        mine
+                    })
+                })
+                .collect();
+
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        });
+
+        while let Some(value) = stack.pop() {
+            popped.push(value);
+        }
+
+        popped.sort_unstable();
+        assert_eq!(popped.len(), THREADS * PER_THREAD);
+        assert!(popped.iter().enumerate().all(|(i, &value)| i == value));
+    }
+
+    #[test]
+    fn poppers_only() {
+        // Fill it up first, then everyone fights over the same few heads
+        const TOTAL: usize = 100_000;
+        let stack = ConcurrentStack::new();
+        for i in 0..TOTAL {
+            stack.push(i);
+        }
+
+        let mut popped: Vec<usize> = thread::scope(|s| {
+            let workers: Vec<_> = (0..8)
+                .map(|_| s.spawn(|| std::iter::from_fn(|| stack.pop()).collect::<Vec<_>>()))
+                .collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        });
+
+        popped.sort_unstable();
+        assert!(popped.into_iter().eq(0..TOTAL));
+    }
+
+    #[test]
+    fn drops_everything() {
+        let tracker = Arc::new(());
+        {
+            let stack = ConcurrentStack::new();
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            stack.push(tracker.clone());
+                            stack.push(tracker.clone());
+                            drop(stack.pop());
+                        }
+                    });
+                }
+            });
+            assert_eq!(Arc::strong_count(&tracker), 4001);
+        }
+        assert_eq!(Arc::strong_count(&tracker), 1);
+    }
+}
@@ -1,2 +1,3 @@
+pub mod concurrentstack;
 pub mod reflist;
 pub mod threadlist;
@@ -0,0 +1,472 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/*
+* ConcurrentQueue is a lock-free multi producer, multi consumer FIFO
+* (a Michael-Scott queue) built out of the same head/tail singly linked
+* shape as UnsafeList, just with atomic head, tail and next pointers.
+*
+* There's always a sentinel node at the head that holds no element, so
+* head and tail are never null and producers and consumers mostly stay off
+* each other's pointers. The element of the first real node is the next
+* thing to come out.
+*
+* push() hangs the new node off tail.next with a CAS, then tries to swing
+* tail over to it. If tail is lagging (someone else's push got halfway)
+* whoever notices helps swing it along first.
+*
+* pop() CAS-es head over to head.next, moves the element out of that node,
+* and that node becomes the new sentinel. The old sentinel gets retired.
+*
+* Retired nodes get freed with hazard pointers: pop() publishes the head
+* and head.next it's about to look at (push() publishes the tail) and checks
+* they're still current, and nodes only get freed once nobody has them
+* published. Hazard slots belong to the queue, each operation borrows one.
+*/
+
+pub struct ConcurrentQueue<T> {
+    head: AtomicPtr<Node<T>>,
+    tail: AtomicPtr<Node<T>>,
+    // Append only, freed when the queue is
+    hazards: AtomicPtr<HazardSlot>,
+    slots: AtomicUsize,
+    // Old sentinels waiting to be freed, chained through retired_next
+    retired: AtomicPtr<Node<T>>,
+    retired_count: AtomicUsize,
+}
+
+struct Node<T> {
+    // Filled in before the node is published, moved out by the pop that
+    // makes this node the sentinel. Never dropped in place.
+    elem: UnsafeCell<MaybeUninit<T>>,
+    next: AtomicPtr<Node<T>>,
+    // Only the retiring thread and the scanning thread ever touch this
+    retired_next: *mut Node<T>,
+}
+
+struct HazardSlot {
+    protected: [AtomicPtr<()>; 2],
+    in_use: AtomicBool,
+    next: *mut HazardSlot,
+}
+
+// How many retired nodes we let pile up before we go scanning
+const RETIRE_THRESHOLD: usize = 64;
+
+impl<T> Node<T> {
+    fn alloc(elem: MaybeUninit<T>) -> *mut Node<T> {
+        Box::into_raw(Box::new(Node {
+            elem: UnsafeCell::new(elem),
+            next: AtomicPtr::new(ptr::null_mut()),
+            retired_next: ptr::null_mut(),
+        }))
+    }
+}
+
+impl HazardSlot {
+    // Publish ptr, then check it's still what src holds. If it is, it
+    // can't be freed until we publish something else.
+    fn protect<T>(&self, index: usize, ptr: *mut Node<T>, src: &AtomicPtr<Node<T>>) -> bool {
+        self.protected[index].store(ptr.cast(), Ordering::SeqCst);
+        src.load(Ordering::SeqCst) == ptr
+    }
+
+    fn release(&self) {
+        for hazard in &self.protected {
+            hazard.store(ptr::null_mut(), Ordering::Release);
+        }
+        self.in_use.store(false, Ordering::Release);
+    }
+}
+
+impl<T> ConcurrentQueue<T> {
+    pub fn new() -> Self {
+        let sentinel = Node::alloc(MaybeUninit::uninit());
+
+        ConcurrentQueue {
+            head: AtomicPtr::new(sentinel),
+            tail: AtomicPtr::new(sentinel),
+            hazards: AtomicPtr::new(ptr::null_mut()),
+            slots: AtomicUsize::new(0),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            retired_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn push(&self, elem: T) {
+        let node = Node::alloc(MaybeUninit::new(elem));
+        let slot = self.acquire_slot();
+
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            if !slot.protect(0, tail, &self.tail) {
+                continue;
+            }
+
+            let next = unsafe { (*tail).next.load(Ordering::Acquire) };
+            if !next.is_null() {
+                // Tail's behind, help it along and try again
+                let _ = self.tail.compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+
+            let linked = unsafe {
+                (*tail)
+                    .next
+                    .compare_exchange(ptr::null_mut(), node, Ordering::Release, Ordering::Relaxed)
+                    .is_ok()
+            };
+            if linked {
+                // If this fails somebody already helped us
+                let _ = self.tail.compare_exchange(tail, node, Ordering::Release, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        slot.release();
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let slot = self.acquire_slot();
+
+        let popped = loop {
+            let head = self.head.load(Ordering::Acquire);
+            if !slot.protect(0, head, &self.head) {
+                continue;
+            }
+
+            let next = unsafe { (*head).next.load(Ordering::Acquire) };
+            if next.is_null() {
+                break None;
+            }
+            // head.next never changes once it's set, so if head is still
+            // the head, next hasn't been popped past either
+            slot.protected[1].store(next.cast(), Ordering::SeqCst);
+            if self.head.load(Ordering::SeqCst) != head {
+                continue;
+            }
+
+            let tail = self.tail.load(Ordering::Acquire);
+            if head == tail {
+                // Not empty, tail's just lagging. Don't let head pass it.
+                let _ = self.tail.compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                // next is the sentinel now and its elem is ours. It's still
+                // published, so nobody can free it out from under us.
+                let elem = unsafe { (*(*next).elem.get()).assume_init_read() };
+                break Some((head, elem));
+            }
+        };
+
+        slot.release();
+
+        popped.map(|(old_sentinel, elem)| {
+            unsafe { self.retire(old_sentinel) };
+            elem
+        })
+    }
+
+    // Just a peek, someone might push or pop right after
+    pub fn is_empty(&self) -> bool {
+        let slot = self.acquire_slot();
+        let empty = loop {
+            let head = self.head.load(Ordering::Acquire);
+            if slot.protect(0, head, &self.head) {
+                break unsafe { (*head).next.load(Ordering::Acquire).is_null() };
+            }
+        };
+        slot.release();
+        empty
+    }
+
+    // Finds a free hazard slot, or hangs a new one on the list
+    fn acquire_slot(&self) -> &HazardSlot {
+        let mut cur = self.hazards.load(Ordering::Acquire);
+        while !cur.is_null() {
+            let slot = unsafe { &*cur };
+            if slot
+                .in_use
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return slot;
+            }
+            cur = slot.next;
+        }
+
+        let slot = Box::into_raw(Box::new(HazardSlot {
+            protected: [AtomicPtr::new(ptr::null_mut()), AtomicPtr::new(ptr::null_mut())],
+            in_use: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.hazards.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*slot).next = head };
+            match self.hazards.compare_exchange_weak(head, slot, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        self.slots.fetch_add(1, Ordering::Relaxed);
+        unsafe { &*slot }
+    }
+
+    // node has to be unlinked already, with no elem left in it
+    unsafe fn retire(&self, node: *mut Node<T>) {
+        // Count it before anyone can find it, or a scan could free it and
+        // subtract it before we ever added it
+        let retired = self.retired_count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.push_retired(node);
+
+        if retired >= RETIRE_THRESHOLD.max(4 * self.slots.load(Ordering::Relaxed)) {
+            self.scan();
+        }
+    }
+
+    unsafe fn push_retired(&self, node: *mut Node<T>) {
+        // Only pushes and take-everything swaps on this one, so no ABA
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            (*node).retired_next = head;
+            match self.retired.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    // Takes the whole retired list, frees everything nobody has published,
+    // and puts the rest back for next time
+    unsafe fn scan(&self) {
+        let mut node = self.retired.swap(ptr::null_mut(), Ordering::Acquire);
+        if node.is_null() {
+            return;
+        }
+
+        // Pairs with the publish-then-recheck in push() and pop()
+        fence(Ordering::SeqCst);
+
+        let mut protected = Vec::new();
+        let mut slot = self.hazards.load(Ordering::Acquire);
+        while !slot.is_null() {
+            for hazard in &(*slot).protected {
+                let ptr = hazard.load(Ordering::SeqCst);
+                if !ptr.is_null() {
+                    protected.push(ptr.cast::<Node<T>>());
+                }
+            }
+            slot = (*slot).next;
+        }
+
+        let mut freed = 0;
+        while !node.is_null() {
+            let next = (*node).retired_next;
+            if protected.contains(&node) {
+                self.push_retired(node);
+            } else {
+                // MaybeUninit, so this never drops an elem
+                drop(Box::from_raw(node));
+                freed += 1;
+            }
+            node = next;
+        }
+        self.retired_count.fetch_sub(freed, Ordering::Relaxed);
+    }
+}
+
+impl<T> Default for ConcurrentQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ConcurrentQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Can't look at anything without racing, so don't
+        f.debug_struct("ConcurrentQueue").finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for ConcurrentQueue<T> {
+    fn drop(&mut self) {
+        // &mut self, so nobody else is in here anymore
+        while self.pop().is_some() {}
+
+        unsafe {
+            drop(Box::from_raw(*self.head.get_mut()));
+
+            let mut node = *self.retired.get_mut();
+            while !node.is_null() {
+                let next = (*node).retired_next;
+                drop(Box::from_raw(node));
+                node = next;
+            }
+
+            let mut slot = *self.hazards.get_mut();
+            while !slot.is_null() {
+                let next = (*slot).next;
+                drop(Box::from_raw(slot));
+                slot = next;
+            }
+        }
+    }
+}
+
+// Elements only ever move in and out whole, nobody gets a &T,
+// so T: Send is all we need to share the queue
+unsafe impl<T: Send> Send for ConcurrentQueue<T> {}
+unsafe impl<T: Send> Sync for ConcurrentQueue<T> {}
+
+#[cfg(test)]
+mod test {
+
+    use super::ConcurrentQueue;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let queue = ConcurrentQueue::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+
+        queue.push(1);
+        queue.push(2);
+        queue.push(3);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+
+        queue.push(4);
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), Some(4));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn producers_and_consumers() {
+        const PRODUCERS: usize = 4;
+        const CONSUMERS: usize = 4;
+        const PER_PRODUCER: usize = 25_000;
+
+        let queue = ConcurrentQueue::new();
+        let done = AtomicBool::new(false);
+
+        let seen: Vec<Vec<(usize, usize)>> = thread::scope(|s| {
+            let consumers: Vec<_> = (0..CONSUMERS)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut mine = Vec::new();
+                        loop {
+                            match queue.pop() {
+                                Some(value) => mine.push(value),
+                                None if done.load(Ordering::Acquire) => {
+                                    // Everything's pushed, drain what's left and go
+                                    mine.extend(std::iter::from_fn(|| queue.pop()));
+                                    break;
+                                }
+                                None => thread::yield_now(),
+                            }
+                        }
+                        mine
+                    })
+                })
+                .collect();
+
+            let producers: Vec<_> = (0..PRODUCERS)
+                .map(|p| {
+                    let queue = &queue;
+                    s.spawn(move || {
+                        for seq in 0..PER_PRODUCER {
+                            queue.push((p, seq));
+                        }
+                    })
+                })
+                .collect();
+
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            done.store(true, Ordering::Release);
+
+            consumers.into_iter().map(|c| c.join().unwrap()).collect()
+        });
+
+        // FIFO: every consumer has to see each producer's values in the
+        // order that producer pushed them
+        for values in &seen {
+            let mut last = [None; PRODUCERS];
+            for &(p, seq) in values {
+                assert!(last[p] < Some(seq), "producer {} went backwards", p);
+                last[p] = Some(seq);
+            }
+        }
+
+        // And every value comes out exactly once
+        let mut all: Vec<_> = seen.into_iter().flatten().collect();
+        all.sort_unstable();
+        assert_eq!(all.len(), PRODUCERS * PER_PRODUCER);
+        assert!(all
+            .iter()
+            .enumerate()
+            .all(|(i, &(p, seq))| (p, seq) == (i / PER_PRODUCER, i % PER_PRODUCER)));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn single_thread_order() {
+        // With one producer and one consumer it's just a queue
+        let queue = ConcurrentQueue::new();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..50_000 {
+                    queue.push(i);
+                }
+            });
+            s.spawn(|| {
+                let mut expected = 0;
+                while expected < 50_000 {
+                    if let Some(value) = queue.pop() {
+                        assert_eq!(value, expected);
+                        expected += 1;
+                    }
+                }
+            });
+        });
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn drops_everything() {
+        let tracker = Arc::new(());
+        {
+            let queue = ConcurrentQueue::new();
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            queue.push(tracker.clone());
+                            queue.push(tracker.clone());
+                            drop(queue.pop());
+                        }
+                    });
+                }
+            });
+            assert_eq!(Arc::strong_count(&tracker), 4001);
+        }
+        assert_eq!(Arc::strong_count(&tracker), 1);
+    }
+}
@@ -1 +1,2 @@
+pub mod concurrentqueue;
 pub mod unsafelist;